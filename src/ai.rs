@@ -0,0 +1,87 @@
+// Monster behaviours.
+
+use rand::*;
+
+use entities::*;
+use game::*;
+use map::*;
+
+// Monster turn, dispatched on the monster's AI. Returns the time cost of the action taken.
+pub fn ai_take_turn(monster_id: usize, game: &mut Game, objects: &mut [Object]) -> i32 {
+    use entities::Ai::*;
+    if let Some(ai) = objects[monster_id].ai.take() {
+        let (new_ai, cost) = match ai {
+            Basic => (Basic, ai_basic(monster_id, game, objects)),
+            Wander => (Wander, ai_wander(monster_id, &game.map, game.config.map.corner_rule(), objects, &mut game.rng)),
+            Confused { previous_ai, num_turns } => ai_confused(monster_id, game, objects, previous_ai, num_turns),
+        };
+        objects[monster_id].ai = Some(new_ai);
+        cost
+    } else {
+        COST_WAIT
+    }
+}
+
+fn ai_basic(monster_id: usize, game: &mut Game, objects: &mut [Object]) -> i32 {
+    // A basic monster takes its turn, going after the player if it can see them.
+    let (monster_x, monster_y) = objects[monster_id].pos();
+    let (player_x, player_y) = objects[PLAYER].pos();
+    let rule = game.config.map.corner_rule();
+    if game.views.can_see(monster_id, objects, player_x, player_y, &game.map, &game.config.fov) {
+        let (dx, dy) = (player_x - monster_x, player_y - monster_y);
+        let adjacent = dx.abs() <= 1 && dy.abs() <= 1 && can_pass_corner(monster_x, monster_y, dx, dy, &game.map, rule);
+        if !adjacent {
+            // Move towards player if far away, by the best path there is, or straight at them if
+            // there is none.
+            match find_path((monster_x, monster_y), (player_x, player_y), &game.map, rule, objects, |_, _| true) {
+                Some(path) => {
+                    // Monsters open doors in their way as the player does, by walking into them.
+                    let (next_x, next_y) = path[0];
+                    if !game.map.open(next_x, next_y) {
+                        move_by(monster_id, next_x - monster_x, next_y - monster_y, &game.map, rule, objects);
+                    }
+                }
+                None => move_towards(monster_id, player_x, player_y, &game.map, rule, objects),
+            }
+            return COST_MOVE;
+        } else if objects[PLAYER].alive {
+            // Close enough, attack! (if the player is still alive.)
+            let (monster, player) = mut_two(monster_id, PLAYER, objects);
+            monster.attack(monster_id, player, PLAYER, game);
+            return COST_ATTACK;
+        }
+    }
+    COST_WAIT
+}
+
+fn ai_wander(monster_id: usize, map: &Map, rule: CornerRule, objects: &mut [Object], rng: &mut GameRng) -> i32 {
+    // Pick a random direction (possibly staying put) and try to step that way.
+    let dx = rng.gen_range(-1, 2);
+    let dy = rng.gen_range(-1, 2);
+    if dx == 0 && dy == 0 {
+        return COST_WAIT;
+    }
+    move_by(monster_id, dx, dy, map, rule, objects);
+    COST_MOVE
+}
+
+fn ai_confused(monster_id: usize, game: &mut Game, objects: &mut [Object], previous_ai: Box<Ai>, num_turns: i32) -> (Ai, i32) {
+    if num_turns >= 0 {
+        // Still confused... move in a random direction, and decrease the number of turns confused.
+        let cost = ai_wander(monster_id, &game.map, game.config.map.corner_rule(), objects, &mut game.rng);
+        (Ai::Confused { previous_ai: previous_ai, num_turns: num_turns - 1 }, cost)
+    } else {
+        // Restore the previous AI (this one will be deleted).
+        game.messages.add(format!("The {} is no longer confused!", objects[monster_id].name), COLOR_MSG_ALERT);
+        (*previous_ai, COST_WAIT)
+    }
+}
+
+// Monsters that hunt the player, even if they are confused for now.
+pub fn is_hostile(ai: &Ai) -> bool {
+    match *ai {
+        Ai::Basic => true,
+        Ai::Wander => false,
+        Ai::Confused { ref previous_ai, .. } => is_hostile(previous_ai),
+    }
+}
@@ -0,0 +1,290 @@
+// Game configuration: the screen and map sizes, room sizes, FOV and lighting, read from a TOML
+// file at startup, with command-line overrides.
+
+use std::fs::File;
+use std::io::Read;
+
+use fov::*;
+use map::*;
+
+// The configuration file read by default.
+pub const CONFIG_PATH: &'static str = "config.toml";
+
+// The smallest screen the GUI fits on.
+const MIN_PANEL_HEIGHT: i32 = 5;
+const MIN_SCREEN_WIDTH: i32 = 60;
+
+// Settings read from the configuration file at startup, and from '--set' on the command line.
+// Anything not given keeps its default, which is what the game was designed around; see
+// config.toml for what each setting does.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub screen: ScreenConfig,
+    pub map: MapConfig,
+    pub fov: FovConfig,
+    pub lighting: LightingConfig,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScreenConfig {
+    pub width: i32,
+    pub height: i32,
+    pub tileset: String,        // Checked against the tilesets file when the window is opened.
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MapConfig {
+    pub width: i32,
+    pub height: i32,
+    pub room_min_size: i32,
+    pub room_max_size: i32,
+    pub max_rooms: i32,
+    pub corner_rule: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FovConfig {
+    pub algorithm: String,
+    pub light_walls: bool,
+    pub torch_radius: i32,      // 0 = unlimited.
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LightingConfig {
+    pub ambient_illumination: i32,
+    pub illumination_modulation: f64,
+}
+
+impl Default for ScreenConfig {
+    fn default() -> Self {
+        ScreenConfig { width: 80, height: 50, tileset: "ASCII".into() }
+    }
+}
+
+impl Default for MapConfig {
+    fn default() -> Self {
+        MapConfig { width: 80, height: 45, room_min_size: 10, room_max_size: 15, max_rooms: 30, corner_rule: "NoSqueeze".into() }
+    }
+}
+
+impl Default for FovConfig {
+    fn default() -> Self {
+        FovConfig { algorithm: "Shadowcasting".into(), light_walls: true, torch_radius: 0 }
+    }
+}
+
+impl Default for LightingConfig {
+    fn default() -> Self {
+        LightingConfig { ambient_illumination: 100, illumination_modulation: 0.5 }
+    }
+}
+
+impl Config {
+    // The GUI panel fills the screen below the map.
+    pub fn panel_height(&self) -> i32 {
+        self.screen.height - self.map.height
+    }
+    
+    // Whether games play out the same under both configurations. Only the screen may differ, as it
+    // only changes how the game looks.
+    pub fn plays_like(&self, other: &Config) -> bool {
+        self.map == other.map && self.fov == other.fov && self.lighting == other.lighting
+    }
+    
+    // Check the settings for values the game can't work with, returning a description of every
+    // problem found.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        
+        let (screen, map) = (&self.screen, &self.map);
+        if screen.width < MIN_SCREEN_WIDTH {
+            errors.push(format!("screen.width must be at least {}, got {}", MIN_SCREEN_WIDTH, screen.width));
+        }
+        if map.width < 1 || map.height < 1 {
+            errors.push(format!("map.width and map.height must be greater than 0, got {} and {}", map.width, map.height));
+        }
+        if map.width > screen.width {
+            errors.push(format!("map.width ({}) must not be more than screen.width ({})", map.width, screen.width));
+        }
+        if self.panel_height() < MIN_PANEL_HEIGHT {
+            errors.push(format!("screen.height ({}) must be at least map.height ({}) plus {} rows for the panel",
+                                screen.height, map.height, MIN_PANEL_HEIGHT));
+        }
+        
+        // Rooms need at least one floor tile inside their walls, and must fit on the map with a
+        // tile to spare.
+        if map.room_min_size < 3 {
+            errors.push(format!("map.room_min_size must be at least 3, got {}", map.room_min_size));
+        }
+        if map.room_min_size > map.room_max_size {
+            errors.push(format!("map.room_min_size ({}) must not be more than map.room_max_size ({})", map.room_min_size, map.room_max_size));
+        }
+        if map.room_max_size >= map.width || map.room_max_size >= map.height {
+            errors.push(format!("map.room_max_size ({}) must be less than both map.width ({}) and map.height ({})",
+                                map.room_max_size, map.width, map.height));
+        }
+        if map.max_rooms < 1 {
+            errors.push(format!("map.max_rooms must be greater than 0, got {}", map.max_rooms));
+        }
+        if !CORNER_RULES.iter().any(|&(name, _)| name == map.corner_rule) {
+            let names: Vec<&str> = CORNER_RULES.iter().map(|&(name, _)| name).collect();
+            errors.push(format!("map.corner_rule '{}' is not one of {}", map.corner_rule, names.join(", ")));
+        }
+        
+        if !FOV_ALGORITHMS.iter().any(|&(name, _)| name == self.fov.algorithm) {
+            let names: Vec<&str> = FOV_ALGORITHMS.iter().map(|&(name, _)| name).collect();
+            errors.push(format!("fov.algorithm '{}' is not one of {}", self.fov.algorithm, names.join(", ")));
+        }
+        if self.fov.torch_radius < 0 {
+            errors.push(format!("fov.torch_radius must not be negative, got {}", self.fov.torch_radius));
+        }
+        
+        let lighting = &self.lighting;
+        if lighting.ambient_illumination < 0 || lighting.ambient_illumination > 9999 {
+            errors.push(format!("lighting.ambient_illumination must be between 0 and 9999, got {}", lighting.ambient_illumination));
+        }
+        if lighting.illumination_modulation.is_nan() || lighting.illumination_modulation <= 0.0 {
+            errors.push(format!("lighting.illumination_modulation must be greater than 0, got {}", lighting.illumination_modulation));
+        }
+        
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl MapConfig {
+    pub fn corner_rule(&self) -> CornerRule {
+        CORNER_RULES.iter().find(|&&(name, _)| name == self.corner_rule)
+            .map(|&(_, rule)| rule)
+            .expect("the corner rule is checked when the configuration is loaded")
+    }
+}
+
+impl FovConfig {
+    pub fn algorithm(&self) -> FovAlgorithm {
+        FOV_ALGORITHMS.iter().find(|&&(name, _)| name == self.algorithm)
+            .map(|&(_, algorithm)| algorithm)
+            .expect("the FOV algorithm is checked when the configuration is loaded")
+    }
+}
+
+// Load the configuration file, if there is one, apply the command-line settings on top, and
+// validate the result.
+pub fn load_config(path: &str, settings: &[String]) -> Result<Config, String> {
+    let mut config = if std::path::Path::new(path).exists() {
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|e| format!("Could not read configuration file '{}': {}", path, e))?;
+        text.parse::<toml::Value>()
+            .map_err(|e| format!("Could not parse configuration file '{}': {}", path, e))?
+    } else {
+        toml::Value::Table(Default::default())
+    };
+    for setting in settings {
+        apply_setting(&mut config, setting).map_err(|e| format!("Invalid setting '{}': {}", setting, e))?;
+    }
+    
+    let config: Config = config.try_into()
+        .map_err(|e| format!("Invalid configuration: {}", e))?;
+    config.validate().map_err(|errors| {
+        format!("Invalid configuration:\n    {}", errors.join("\n    "))
+    })?;
+    Ok(config)
+}
+
+// Apply a command-line setting, "section.key=value", to the configuration. Values are written as
+// in the file, except that strings don't need quotes.
+fn apply_setting(config: &mut toml::Value, setting: &str) -> Result<(), String> {
+    let mut parts = setting.splitn(2, '=');
+    let (key, value) = match (parts.next(), parts.next()) {
+        (Some(key), Some(value)) => (key.trim(), value.trim()),
+        _ => return Err("expected section.key=value".into()),
+    };
+    let mut path = key.splitn(2, '.');
+    let (section, name) = match (path.next(), path.next()) {
+        (Some(section), Some(name)) => (section, name),
+        _ => return Err("expected section.key=value".into()),
+    };
+    let value = format!("value = {}", value).parse::<toml::Value>().ok()
+        .and_then(|parsed| parsed.get("value").cloned())
+        .unwrap_or_else(|| toml::Value::String(value.into()));
+    
+    let table = config.as_table_mut().expect("a TOML document is a table");
+    let section_table = table.entry(section.to_string()).or_insert_with(|| toml::Value::Table(Default::default()));
+    match section_table.as_table_mut() {
+        Some(section_table) => {
+            section_table.insert(name.into(), value);
+            Ok(())
+        }
+        None => Err(format!("'{}' is not a section", section)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn settings(settings: &[&str]) -> Vec<String> {
+        settings.iter().map(|setting| setting.to_string()).collect()
+    }
+    
+    #[test]
+    fn defaults_and_the_shipped_file_are_valid() {
+        assert_eq!(Config::default().validate(), Ok(()));
+        assert!(load_config(CONFIG_PATH, &[]).is_ok());
+    }
+    
+    #[test]
+    fn settings_override_the_file() {
+        let config = load_config(CONFIG_PATH, &settings(&["map.max_rooms=3", "map.corner_rule=NoCutting", "fov.algorithm=Diamond", "fov.light_walls=false"])).unwrap();
+        assert_eq!(config.map.max_rooms, 3);
+        assert_eq!(config.map.corner_rule(), CornerRule::NoCutting);
+        assert_eq!(config.fov.algorithm(), FovAlgorithm::Diamond);
+        assert!(!config.fov.light_walls);
+    }
+    
+    #[test]
+    fn malformed_settings_are_rejected() {
+        for setting in &["map.max_rooms", "max_rooms=3", "map.colour=red", "map.max_rooms=many"] {
+            assert!(load_config("no such file", &settings(&[setting])).is_err(), "{} was accepted", setting);
+        }
+    }
+    
+    // Every problem is reported at once, not just the first.
+    #[test]
+    fn every_problem_is_reported() {
+        let mut config = Config::default();
+        config.map.width = 100;
+        config.map.room_min_size = 2;
+        config.map.corner_rule = "Sideways".into();
+        config.fov.algorithm = "Guesswork".into();
+        config.fov.torch_radius = -1;
+        config.lighting.illumination_modulation = f64::NAN;
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 6, "{:?}", errors);
+        assert!(errors[0].starts_with("map.width (100) must not be more than screen.width"), "{:?}", errors);
+        assert!(errors.iter().any(|error| error.starts_with("map.corner_rule 'Sideways' is not one of Allow")));
+        assert!(errors.iter().any(|error| error.starts_with("fov.algorithm 'Guesswork' is not one of Shadowcasting")));
+    }
+    
+    // Rooms must fit on the map with a tile to spare, and have floor inside their walls.
+    #[test]
+    fn rooms_must_fit_the_map() {
+        let mut config = Config {
+            map: MapConfig { width: 10, height: 8, room_min_size: 3, room_max_size: 8, max_rooms: 1, ..Default::default() },
+            ..Default::default()
+        };
+        assert_eq!(config.validate().unwrap_err().len(), 1);
+        config.map.room_max_size = 7;
+        assert_eq!(config.validate(), Ok(()));
+    }
+}
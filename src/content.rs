@@ -0,0 +1,361 @@
+// Monster, item and feature templates, loaded from content.ron, and the objects spawned from them.
+
+use std::fs::File;
+use std::io::Read;
+use std::collections::HashSet;
+
+use entities::*;
+
+// Monster, item and feature templates. Those listed as required are spawned by name by the game
+// itself, so must always be defined.
+pub const CONTENT_PATH: &'static str = "content.ron";
+const REQUIRED_MONSTERS: &'static [&'static str] = &["cat buddy"];
+const REQUIRED_ITEMS: &'static [&'static str] = &["lantern"];
+const REQUIRED_FEATURES: &'static [&'static str] = &["flickering light bulb", "failing light bulb"];
+
+// Content templates, as loaded from CONTENT_PATH.
+#[derive(Debug, Deserialize)]
+pub struct Content {
+    pub monsters: Vec<MonsterTemplate>,
+    pub items: Vec<ItemTemplate>,
+    pub features: Vec<FeatureTemplate>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LightTemplate {
+    pub intensity: i32,
+    pub colour: (i32, i32, i32),
+    #[serde(default)]
+    pub flicker: i32,
+    #[serde(default)]
+    pub fuel: Option<i32>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct MonsterTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub colour: (f64, f64, f64),
+    pub hp: i32,
+    pub defense: i32,
+    pub power: i32,
+    pub speed: i32,
+    pub ai: Ai,
+    #[serde(default)]
+    pub light: Option<LightTemplate>,
+    pub depth: (i32, i32),
+    pub weight: u32,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct EquipmentTemplate {
+    pub slot: Slot,
+    #[serde(default)]
+    pub max_hp_bonus: i32,
+    #[serde(default)]
+    pub power_bonus: i32,
+    #[serde(default)]
+    pub defense_bonus: i32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ItemTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub colour: (f64, f64, f64),
+    pub kind: Item,
+    #[serde(default)]
+    pub equipment: Option<EquipmentTemplate>,
+    #[serde(default)]
+    pub light: Option<LightTemplate>,
+    pub depth: (i32, i32),
+    pub weight: u32,
+}
+
+// Scenery that is neither an actor nor an item, such as light fittings.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FeatureTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub colour: (f64, f64, f64),
+    #[serde(default)]
+    pub blocks: bool,
+    #[serde(default)]
+    pub light: Option<LightTemplate>,
+}
+
+impl Content {
+    pub fn monster(&self, name: &str) -> &MonsterTemplate {
+        self.monsters.iter().find(|t| t.name == name).expect("required monster template missing")
+    }
+    
+    pub fn item(&self, name: &str) -> &ItemTemplate {
+        self.items.iter().find(|t| t.name == name).expect("required item template missing")
+    }
+    
+    pub fn feature(&self, name: &str) -> &FeatureTemplate {
+        self.features.iter().find(|t| t.name == name).expect("required feature template missing")
+    }
+    
+    // Check the templates for mistakes that deserialization alone can't catch, returning a
+    // description of every problem found.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+        
+        let mut names = HashSet::new();
+        for template in &self.monsters {
+            let what = format!("monster '{}'", template.name);
+            validate_name(&template.name, &what, &mut names, &mut errors);
+            validate_colour(template.colour, &what, &mut errors);
+            validate_spawn(template.depth, &what, &mut errors);
+            if template.hp <= 0 {
+                errors.push(format!("{}: hp must be greater than 0, got {}", what, template.hp));
+            }
+            if template.speed <= 0 {
+                errors.push(format!("{}: speed must be greater than 0, got {}", what, template.speed));
+            }
+            if let Ai::Confused { .. } = template.ai {
+                errors.push(format!("{}: ai must be Basic or Wander, Confused is only applied by spells", what));
+            }
+            if let Some(ref light) = template.light {
+                validate_light(light, &what, &mut errors);
+            }
+        }
+        
+        let mut names = HashSet::new();
+        for template in &self.items {
+            let what = format!("item '{}'", template.name);
+            validate_name(&template.name, &what, &mut names, &mut errors);
+            validate_colour(template.colour, &what, &mut errors);
+            validate_spawn(template.depth, &what, &mut errors);
+            match template.kind {
+                Item::Torch | Item::Lantern => {
+                    if template.light.is_none() {
+                        errors.push(format!("{}: kind {:?} needs a light", what, template.kind));
+                    }
+                    if template.equipment.is_none_or(|e| e.slot != Slot::OffHand) {
+                        errors.push(format!("{}: kind {:?} needs equipment with slot OffHand", what, template.kind));
+                    }
+                }
+                Item::Equipment => {
+                    if template.equipment.is_none() {
+                        errors.push(format!("{}: kind Equipment needs an equipment entry", what));
+                    }
+                }
+                _ => {
+                    if template.equipment.is_some() {
+                        errors.push(format!("{}: kind {:?} cannot have an equipment entry", what, template.kind));
+                    }
+                }
+            }
+            if let Some(ref light) = template.light {
+                validate_light(light, &what, &mut errors);
+            }
+        }
+        
+        let mut names = HashSet::new();
+        for template in &self.features {
+            let what = format!("feature '{}'", template.name);
+            validate_name(&template.name, &what, &mut names, &mut errors);
+            validate_colour(template.colour, &what, &mut errors);
+            if let Some(ref light) = template.light {
+                validate_light(light, &what, &mut errors);
+            }
+        }
+        
+        for name in REQUIRED_MONSTERS {
+            if !self.monsters.iter().any(|t| t.name == *name) {
+                errors.push(format!("monster '{}' is required by the game but not defined", name));
+            }
+        }
+        for name in REQUIRED_ITEMS {
+            if !self.items.iter().any(|t| t.name == *name) {
+                errors.push(format!("item '{}' is required by the game but not defined", name));
+            }
+        }
+        for name in REQUIRED_FEATURES {
+            if !self.features.iter().any(|t| t.name == *name) {
+                errors.push(format!("feature '{}' is required by the game but not defined", name));
+            }
+        }
+        
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn validate_name(name: &str, what: &str, names: &mut HashSet<String>, errors: &mut Vec<String>) {
+    if name.is_empty() {
+        errors.push(format!("{}: name must not be empty", what));
+    } else if !names.insert(name.to_string()) {
+        errors.push(format!("{}: name is defined more than once", what));
+    }
+}
+
+fn validate_colour(colour: (f64, f64, f64), what: &str, errors: &mut Vec<String>) {
+    let (h, s, l) = colour;
+    if !(0.0..=360.0).contains(&h) || !(0.0..=1.0).contains(&s) || !(0.0..=1.0).contains(&l) {
+        errors.push(format!("{}: colour {:?} is not a valid HSL triple (hue 0 -> 360, saturation and lightness 0.0 -> 1.0)", what, colour));
+    }
+}
+
+fn validate_spawn(depth: (i32, i32), what: &str, errors: &mut Vec<String>) {
+    if depth.0 < 1 || depth.0 > depth.1 {
+        errors.push(format!("{}: depth range {:?} must start at 1 or deeper, and not end before it starts", what, depth));
+    }
+}
+
+fn validate_light(light: &LightTemplate, what: &str, errors: &mut Vec<String>) {
+    if light.intensity <= 0 {
+        errors.push(format!("{}: light intensity must be greater than 0, got {}", what, light.intensity));
+    }
+    if light.flicker < 0 || light.flicker > light.intensity {
+        errors.push(format!("{}: light flicker must be between 0 and the intensity ({}), got {}", what, light.intensity, light.flicker));
+    }
+    if light.fuel.is_some_and(|fuel| fuel <= 0) {
+        errors.push(format!("{}: light fuel must be greater than 0 if given", what));
+    }
+    let (r, g, b) = light.colour;
+    if !(0..=255).contains(&r) || !(0..=255).contains(&g) || !(0..=255).contains(&b) {
+        errors.push(format!("{}: light colour {:?} is not a valid RGB triple (0 -> 255)", what, light.colour));
+    }
+}
+
+// Load and validate the content templates.
+pub fn load_content(path: &str) -> Result<Content, String> {
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|e| format!("Could not read content file '{}': {}", path, e))?;
+    
+    let content: Content = ron::de::from_str(&text)
+        .map_err(|e| format!("Could not parse content file '{}': {}", path, e))?;
+    
+    content.validate().map_err(|errors| {
+        format!("Invalid content file '{}':\n    {}", path, errors.join("\n    "))
+    })?;
+    
+    Ok(content)
+}
+
+impl LightTemplate {
+    // Give an object this light source, lit or not.
+    pub fn apply(&self, object: &mut Object, lit: bool) {
+        object.light_source = (lit, self.intensity, self.colour);
+        object.light_intensity = self.intensity;
+        object.light_flicker = self.flicker;
+        object.light_fuel = self.fuel;
+    }
+}
+
+impl MonsterTemplate {
+    pub fn spawn(&self, x: i32, y: i32) -> Object {
+        let mut monster = Object::new(x, y, self.glyph, &self.name, self.colour, true, (false, 0, (0, 0, 0)));
+        monster.alive = true;
+        monster.speed = self.speed;
+        monster.fighter = Some(Fighter { base_max_hp: self.hp, hp: self.hp, max_mana: 0, mana: 0, base_defense: self.defense, base_power: self.power, on_death: DeathCallback::Monster });
+        monster.ai = Some(self.ai.clone());
+        if let Some(ref light) = self.light {
+            light.apply(&mut monster, true);
+        }
+        monster
+    }
+}
+
+impl ItemTemplate {
+    // Items with a light source start unlit; it is lit when the item is equipped.
+    pub fn spawn(&self, x: i32, y: i32) -> Object {
+        let mut item = Object::new(x, y, self.glyph, &self.name, self.colour, false, (false, 0, (0, 0, 0)));
+        item.item = Some(self.kind);
+        item.equipment = self.equipment.map(|e| Equipment {
+            slot: e.slot,
+            equipped: false,
+            max_hp_bonus: e.max_hp_bonus,
+            power_bonus: e.power_bonus,
+            defense_bonus: e.defense_bonus,
+        });
+        if let Some(ref light) = self.light {
+            light.apply(&mut item, false);
+        }
+        item
+    }
+}
+
+impl FeatureTemplate {
+    pub fn spawn(&self, x: i32, y: i32) -> Object {
+        let mut feature = Object::new(x, y, self.glyph, &self.name, self.colour, self.blocks, (false, 0, (0, 0, 0)));
+        if let Some(ref light) = self.light {
+            light.apply(&mut feature, true);
+        }
+        feature
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn content() -> Content {
+        load_content(CONTENT_PATH).expect("the content file loads")
+    }
+    
+    #[test]
+    fn shipped_content_is_valid() {
+        let content = content();
+        assert_eq!(content.validate(), Ok(()));
+        let orc = content.monster("orc").spawn(3, 4);
+        assert_eq!((orc.pos(), orc.alive, orc.blocks), ((3, 4), true, true));
+    }
+    
+    #[test]
+    fn bad_templates_are_reported() {
+        let mut content = content();
+        content.monsters[0].hp = 0;
+        content.monsters[1].name = content.monsters[0].name.clone();
+        content.items[0].colour = (400.0, 0.5, 0.5);
+        content.features[0].light = Some(LightTemplate { intensity: 2, colour: (0, 0, 300), flicker: 3, fuel: Some(0) });
+        let errors = content.validate().unwrap_err();
+        let expected = [
+            ": hp must be greater than 0, got 0",
+            ": name is defined more than once",
+            "is not a valid HSL triple",
+            ": light flicker must be between 0 and the intensity (2), got 3",
+            ": light fuel must be greater than 0 if given",
+            "light colour (0, 0, 300) is not a valid RGB triple",
+        ];
+        assert_eq!(errors.len(), expected.len(), "{:?}", errors);
+        for (error, expected) in errors.iter().zip(expected.iter()) {
+            assert!(error.contains(expected), "expected '{}', got '{}'", expected, error);
+        }
+    }
+    
+    #[test]
+    fn required_templates_must_be_defined() {
+        let mut content = content();
+        content.monsters.retain(|template| template.name != "cat buddy");
+        content.features.clear();
+        let errors = content.validate().unwrap_err();
+        assert_eq!(errors, vec![
+            "monster 'cat buddy' is required by the game but not defined".to_string(),
+            "feature 'flickering light bulb' is required by the game but not defined".to_string(),
+            "feature 'failing light bulb' is required by the game but not defined".to_string(),
+        ]);
+    }
+    
+    // Light sources and equipment must match the kind of item.
+    #[test]
+    fn item_kinds_are_checked() {
+        let mut content = content();
+        let lantern = content.items.iter().position(|template| template.name == "lantern").unwrap();
+        content.items[lantern].equipment = None;
+        content.items[lantern].light = None;
+        let errors = content.validate().unwrap_err();
+        assert_eq!(errors, vec![
+            "item 'lantern': kind Lantern needs a light".to_string(),
+            "item 'lantern': kind Lantern needs equipment with slot OffHand".to_string(),
+        ]);
+    }
+}
@@ -0,0 +1,325 @@
+// The effects layer: short-lived particles (sparks, smoke, blood), projectiles, screen flashes and
+// bursts of light, shown over the map for what the game reports happening in its cues. Effects are
+// animated a frame at a time, drawn over the finished map, and never hold up the game: they only
+// change what is drawn, so games play the same whether they are shown or not.
+
+use std::cmp;
+use std::mem;
+
+use rand;
+use tcod::colors::Color;
+use tcod::console::*;
+
+use entities::*;
+use fov::*;
+use game::*;
+use lighting::*;
+use map::*;
+use rendering::*;
+
+// Fireballs fly this many tiles a second, trailing flame behind them.
+const FIREBALL_SPEED: f64 = 30.0;
+const FIREBALL_TRAIL: usize = 4;
+const COLOR_FIREBALL: (f64, f64, f64) = (25.0, 1.0, 0.6);
+
+// Lightning reaches its target almost at once, then lingers, flickering, for a moment.
+const LIGHTNING_SPEED: f64 = 200.0;
+const LIGHTNING_LINGER: f64 = 0.25;
+const COLOR_LIGHTNING: (f64, f64, f64) = (200.0, 0.6, 0.85);
+
+// Screen flashes tint the whole map, fading out over their lifetime.
+const FLASH_LIFETIME: f64 = 0.3;
+const FLASH_STRENGTH: f64 = 0.5;
+
+// Bursts of light, as (intensity, lifetime in seconds), fading out linearly.
+const EXPLOSION_BURST: (i32, f64) = (1500, 0.6);
+const LIGHTNING_BURST: (i32, f64) = (900, 0.3);
+
+// Particles thrown out by each kind of event.
+const SPARKS_PER_RADIUS: usize = 8;
+const SMOKE_PER_RADIUS: usize = 3;
+const BLOOD_DROPS: usize = 4;
+const DEATH_BLOOD_DROPS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ParticleKind {
+    Spark,
+    Smoke,
+    Blood,
+}
+
+impl ParticleKind {
+    // The glyph and colour it is drawn with (the colour at its brightest), how long it lasts, in
+    // seconds, and how quickly it slows down, as the fraction of its speed lost each second.
+    fn look(self) -> (char, (f64, f64, f64), f64, f64) {
+        match self {
+            ParticleKind::Spark => ('*', (45.0, 1.0, 0.8), 0.5, 1.5),
+            ParticleKind::Smoke => ('~', (0.0, 0.0, 0.45), 1.5, 0.8),
+            ParticleKind::Blood => (',', (0.0, 0.9, 0.35), 1.2, 4.0),
+        }
+    }
+}
+
+// A particle, moving freely across the map (in tiles, and tiles a second), drawn in whichever tile
+// it is in.
+struct Particle {
+    kind: ParticleKind,
+    x: f64,
+    y: f64,
+    dx: f64,
+    dy: f64,
+    age: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ProjectileKind {
+    Lightning,
+    Fireball(i32),      // Exploding at the end of its path, with this radius.
+}
+
+// A projectile, following a path of tiles.
+struct Projectile {
+    kind: ProjectileKind,
+    path: Vec<(i32, i32)>,
+    age: f64,
+}
+
+impl Projectile {
+    fn speed(&self) -> f64 {
+        match self.kind {
+            ProjectileKind::Lightning => LIGHTNING_SPEED,
+            ProjectileKind::Fireball(_) => FIREBALL_SPEED,
+        }
+    }
+    
+    // How far along its path it has got, as an index into the path.
+    fn reached(&self) -> usize {
+        cmp::min((self.age * self.speed()) as usize, self.path.len() - 1)
+    }
+    
+    fn arrived(&self) -> bool {
+        self.age * self.speed() >= (self.path.len() - 1) as f64
+    }
+    
+    fn finished(&self) -> bool {
+        match self.kind {
+            ProjectileKind::Lightning => self.age >= (self.path.len() - 1) as f64 / self.speed() + LIGHTNING_LINGER,
+            ProjectileKind::Fireball(_) => self.arrived(),
+        }
+    }
+}
+
+// The whole map tinted towards a colour, fading out.
+struct ScreenFlash {
+    colour: (f64, f64, f64),
+    age: f64,
+}
+
+// A burst of light, added to the light field while it lasts, fading out.
+struct LightBurst {
+    x: i32,
+    y: i32,
+    intensity: i32,
+    lifetime: f64,
+    age: f64,
+}
+
+// The effects being shown, and whether they have changed the light since it was last worked out.
+#[derive(Default)]
+pub struct Effects {
+    particles: Vec<Particle>,
+    projectiles: Vec<Projectile>,
+    flashes: Vec<ScreenFlash>,
+    bursts: Vec<LightBurst>,
+    relight: bool,
+}
+
+impl Effects {
+    pub fn new() -> Effects {
+        Default::default()
+    }
+    
+    // Start the effects for what the game reports happening.
+    pub fn start(&mut self, cues: &[Cue]) {
+        for &cue in cues {
+            match cue {
+                Cue::Wound(x, y) => self.spray(ParticleKind::Blood, x, y, BLOOD_DROPS, 1.5),
+                Cue::Death(x, y) => self.spray(ParticleKind::Blood, x, y, DEATH_BLOOD_DROPS, 2.5),
+                Cue::Lightning(from, to) => {
+                    self.projectiles.push(Projectile { kind: ProjectileKind::Lightning, path: bresenham(from.0, from.1, to.0, to.1), age: 0.0 });
+                    self.flashes.push(ScreenFlash { colour: COLOR_LIGHTNING, age: 0.0 });
+                    self.burst(to.0, to.1, LIGHTNING_BURST);
+                }
+                Cue::Fireball(from, to, radius) => {
+                    let path = bresenham(from.0, from.1, to.0, to.1);
+                    self.projectiles.push(Projectile { kind: ProjectileKind::Fireball(radius), path: path, age: 0.0 });
+                }
+            }
+        }
+    }
+    
+    // Move every effect on by a frame of the given length, in seconds, ending those that are over.
+    pub fn advance(&mut self, seconds: f64) {
+        // Lights that were shining must be taken away again when they go out.
+        self.relight |= !self.bursts.is_empty();
+        
+        for particle in &mut self.particles {
+            let (_, _, _, drag) = particle.kind.look();
+            let slowing = (1.0 - drag * seconds).max(0.0);
+            particle.x += particle.dx * seconds;
+            particle.y += particle.dy * seconds;
+            particle.dx *= slowing;
+            particle.dy *= slowing;
+            particle.age += seconds;
+        }
+        self.particles.retain(|particle| particle.age < particle.kind.look().2);
+        
+        let mut explosions = vec![];
+        for projectile in &mut self.projectiles {
+            projectile.age += seconds;
+            if let (ProjectileKind::Fireball(radius), true) = (projectile.kind, projectile.arrived()) {
+                explosions.push((*projectile.path.last().unwrap(), radius));
+            }
+        }
+        self.projectiles.retain(|projectile| !projectile.finished());
+        for ((x, y), radius) in explosions {
+            self.explode(x, y, radius);
+        }
+        
+        for flash in &mut self.flashes {
+            flash.age += seconds;
+        }
+        self.flashes.retain(|flash| flash.age < FLASH_LIFETIME);
+        
+        for burst in &mut self.bursts {
+            burst.age += seconds;
+        }
+        self.bursts.retain(|burst| burst.age < burst.lifetime);
+    }
+    
+    // End every effect at once, for fast play.
+    pub fn skip(&mut self) {
+        self.relight |= !self.bursts.is_empty();
+        self.particles.clear();
+        self.projectiles.clear();
+        self.flashes.clear();
+        self.bursts.clear();
+    }
+    
+    // Whether the light has changed since this was last asked, so must be worked out again.
+    pub fn take_relight(&mut self) -> bool {
+        mem::replace(&mut self.relight, false)
+    }
+    
+    // The bursts of light, as light sources, each with how strongly it is shining (0.0 -> 1.0).
+    pub fn lights(&self) -> Vec<(Object, f64)> {
+        self.bursts.iter()
+            .map(|burst| (light_flash(burst.x, burst.y, (burst.intensity, 0)), 1.0 - burst.age / burst.lifetime))
+            .collect()
+    }
+    
+    // Draw the effects over the map on the root console. Particles and projectiles are only seen
+    // where the player can see; flashes tint the whole map.
+    pub fn draw(&self, root: &mut Offscreen, map: &Map, fov_map: &FovMap) {
+        let (map_width, map_height) = map_size(map);
+        let in_view = |x: i32, y: i32| x >= 0 && x < map_width && y >= 0 && y < map_height && fov_map.is_in_fov(x, y);
+        
+        for particle in &self.particles {
+            let (x, y) = (particle.x.round() as i32, particle.y.round() as i32);
+            if in_view(x, y) {
+                let (glyph, colour, lifetime, _) = particle.kind.look();
+                let fading = 1.0 - particle.age / lifetime;
+                root.set_char(x, y, glyph);
+                root.set_char_foreground(x, y, return_rgb_colour((colour.0, colour.1, colour.2 * fading)));
+            }
+        }
+        
+        for projectile in &self.projectiles {
+            let reached = projectile.reached();
+            match projectile.kind {
+                ProjectileKind::Lightning => {
+                    // The bolt flickers as it fades.
+                    let fading = if rand::random::<bool>() { 1.0 } else { 0.6 };
+                    let colour = (COLOR_LIGHTNING.0, COLOR_LIGHTNING.1, COLOR_LIGHTNING.2 * fading);
+                    for i in 1..reached + 1 {
+                        let (x, y) = projectile.path[i];
+                        if in_view(x, y) {
+                            root.set_char(x, y, bolt_glyph(projectile.path[i - 1], (x, y)));
+                            root.set_char_foreground(x, y, return_rgb_colour(colour));
+                        }
+                    }
+                }
+                ProjectileKind::Fireball(_) => {
+                    let trail_start = reached.saturating_sub(FIREBALL_TRAIL);
+                    for i in trail_start.max(1)..reached + 1 {
+                        let (x, y) = projectile.path[i];
+                        if in_view(x, y) {
+                            let fading = 1.0 - (reached - i) as f64 / (FIREBALL_TRAIL + 1) as f64;
+                            root.set_char(x, y, if i == reached { '*' } else { '.' });
+                            root.set_char_foreground(x, y, return_rgb_colour((COLOR_FIREBALL.0, COLOR_FIREBALL.1, COLOR_FIREBALL.2 * fading)));
+                        }
+                    }
+                }
+            }
+        }
+        
+        for flash in &self.flashes {
+            let strength = FLASH_STRENGTH * (1.0 - flash.age / FLASH_LIFETIME);
+            let colour = return_rgb_colour(flash.colour);
+            for y in 0..map_height {
+                for x in 0..map_width {
+                    let foreground = blend(root.get_char_foreground(x, y), colour, strength);
+                    let background = blend(root.get_char_background(x, y), colour, strength);
+                    root.set_char_foreground(x, y, foreground);
+                    root.set_char_background(x, y, background, BackgroundFlag::Set);
+                }
+            }
+        }
+    }
+    
+    // Throw out particles from a tile, in all directions, at up to 'speed' tiles a second.
+    fn spray(&mut self, kind: ParticleKind, x: i32, y: i32, count: usize, speed: f64) {
+        for _ in 0..count {
+            let angle = rand::random::<f64>() * 2.0 * std::f64::consts::PI;
+            let speed = speed * (0.3 + 0.7 * rand::random::<f64>());
+            self.particles.push(Particle {
+                kind: kind,
+                x: x as f64,
+                y: y as f64,
+                dx: angle.cos() * speed,
+                dy: angle.sin() * speed,
+                age: 0.0,
+            });
+        }
+    }
+    
+    fn burst(&mut self, x: i32, y: i32, burst: (i32, f64)) {
+        self.bursts.push(LightBurst { x: x, y: y, intensity: burst.0, lifetime: burst.1, age: 0.0 });
+        self.relight = true;
+    }
+    
+    // A fireball going off: sparks reaching out to the edge of the blast, smoke, and a flash of light.
+    fn explode(&mut self, x: i32, y: i32, radius: i32) {
+        let spark_speed = radius as f64 * 2.5;
+        self.spray(ParticleKind::Spark, x, y, SPARKS_PER_RADIUS * radius as usize, spark_speed);
+        self.spray(ParticleKind::Smoke, x, y, SMOKE_PER_RADIUS * radius as usize, 1.0);
+        self.flashes.push(ScreenFlash { colour: COLOR_FIREBALL, age: 0.0 });
+        self.burst(x, y, EXPLOSION_BURST);
+    }
+}
+
+// How a bolt is drawn going from one tile to the next.
+fn bolt_glyph(from: (i32, i32), to: (i32, i32)) -> char {
+    match (to.0 - from.0, to.1 - from.1) {
+        (0, _) => '|',
+        (_, 0) => '-',
+        (dx, dy) if dx == dy => '\\',
+        _ => '/',
+    }
+}
+
+// A colour part of the way (0.0 -> 1.0) towards another.
+fn blend(colour: Color, towards: Color, amount: f64) -> Color {
+    let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * amount).round() as u8;
+    Color { r: mix(colour.r, towards.r), g: mix(colour.g, towards.g), b: mix(colour.b, towards.b) }
+}
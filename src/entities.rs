@@ -0,0 +1,372 @@
+// Objects (the player, monsters, items and features) and their parts: fighters, AI, items and
+// equipment.
+
+use std::cmp;
+
+use tcod::console::*;
+use tcod::colors::*;
+
+use rand::*;
+
+use game::*;
+use items::*;
+
+// The player is always the first object in the objects list.
+pub const PLAYER: usize = 0;
+
+pub const COLOR_PLAYER: (f64, f64, f64) = (5.0, 0.99, 1.0);
+const COLOR_CORPSE: (f64, f64, f64) = (0.0, 1.0, 0.35);
+
+// Define our 'Object' object, which will be used to represent all in-world objects.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Object {
+    pub x: i32,
+    pub y: i32,
+    pub char: char,
+    pub name: String,
+    pub hsl: (f64, f64, f64),
+    pub blocks: bool,
+    pub alive: bool,
+    pub speed: i32,
+    pub energy: i32,
+    pub fighter: Option<Fighter>,
+    pub ai: Option<Ai>,
+    pub item: Option<Item>,
+    pub equipment: Option<Equipment>,
+    pub light_source: (bool, i32, (i32, i32, i32)),
+    pub light_intensity: i32,       // Un-flickered light source intensity.
+    pub light_flicker: i32,         // Max random variation in intensity each turn, 0 = steady.
+    pub light_fuel: Option<i32>,    // Turns of fuel remaining, None = never runs out.
+}
+
+// Here we define the 'Object' object methods.
+impl Object {
+    pub fn new(x: i32, y: i32, char: char, name: &str, hsl: (f64, f64, f64), blocks: bool, light_source: (bool, i32, (i32, i32, i32))) -> Self {
+        Object {
+            x: x,
+            y: y,
+            char: char,
+            name: name.into(),
+            hsl: hsl,
+            blocks: blocks,
+            alive: false,
+            speed: NORMAL_SPEED,
+            energy: 0,
+            fighter: None,
+            ai: None,
+            item: None,
+            equipment: None,
+            light_source: light_source,
+            light_intensity: light_source.1,
+            light_flicker: 0,
+            light_fuel: None,
+        }
+    }
+    
+    pub fn pos(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+    
+    pub fn set_pos(&mut self, x: i32, y: i32) {
+        self.x = x;
+        self.y = y;
+    }
+    
+    // Return distance to another object.
+    pub fn distance_to(&self, other: &Object) -> f32 {
+        let dx = other.x - self.x;
+        let dy = other.y - self.y;
+        ((dx.pow(2) + dy.pow(2)) as f32).sqrt()
+    }
+    
+    // Return distance to some co-ordinates.
+    pub fn distance(&self, x: i32, y: i32) -> f32 {
+        (((x - self.x).pow(2) + (y - self.y).pow(2)) as f32).sqrt()
+    }
+    
+    pub fn take_damage(&mut self, damage: i32, messages: &mut Messages) {
+        // Apply damage if possible.
+        if let Some(fighter) = self.fighter.as_mut() {
+            if damage > 0 {
+                fighter.hp -= damage;
+            }
+        }
+        
+        // Check for death, and if so call the death function.
+        if let Some(fighter) = self.fighter {
+            if fighter.hp <= 0 {
+                self.alive = false;
+                fighter.on_death.callback(self, messages);
+            }
+        }
+    }
+    
+    // Heal by the given amount, without going over the maximum. 'id' is this object's.
+    pub fn heal(&mut self, id: usize, amount: i32, game: &Game) {
+        let max_hp = self.max_hp(id, game);
+        if let Some(ref mut fighter) = self.fighter {
+            fighter.hp += amount;
+            if fighter.hp > max_hp {
+                fighter.hp = max_hp;
+            }
+        }
+    }
+    
+    // Attack 'target'. 'id' and 'target_id' are the two objects' ids.
+    pub fn attack(&mut self, id: usize, target: &mut Object, target_id: usize, game: &mut Game) {
+        // A simple formula for attack damage. Attacks on the player are shown in red.
+        let damage = self.power(id, game) - target.defense(target_id, game);
+        let colour = if target_id == PLAYER { COLOR_MSG_BAD } else { COLOR_MSG_INFO };
+        if damage > 0 {
+            game.messages.add(format!("{} attacks {} for {} hit points.", self.name, target.name, damage), colour);
+            target.take_damage(damage, &mut game.messages);
+            game.cues.push(if target.alive { Cue::Wound(target.x, target.y) } else { Cue::Death(target.x, target.y) });
+        } else {
+            game.messages.add(format!("{} attacks {} but it has no effect!", self.name, target.name), colour);
+        }
+    }
+    
+    // Update light source flicker and fuel. Called once per game turn by the scheduler. Returns
+    // true if the light has just run out of fuel and gone out.
+    pub fn tick_light(&mut self, rng: &mut GameRng) -> bool {
+        if !self.light_source.0 {
+            return false;
+        }
+        
+        if let Some(fuel) = self.light_fuel {
+            if fuel <= 0 {
+                self.light_source.0 = false;
+                return true;
+            }
+            self.light_fuel = Some(fuel - 1);
+        }
+        
+        if self.light_flicker > 0 {
+            let flicker = rng.gen_range(-self.light_flicker, self.light_flicker + 1);
+            self.light_source.1 = cmp::max(self.light_intensity + flicker, 0);
+        }
+        false
+    }
+    
+    // Combat stats, including the bonuses from any equipment worn. 'id' is this object's.
+    pub fn power(&self, id: usize, game: &Game) -> i32 {
+        let base_power = self.fighter.map_or(0, |f| f.base_power);
+        let bonus: i32 = get_all_equipped(id, game).iter().map(|e| e.power_bonus).sum();
+        base_power + bonus
+    }
+    
+    pub fn defense(&self, id: usize, game: &Game) -> i32 {
+        let base_defense = self.fighter.map_or(0, |f| f.base_defense);
+        let bonus: i32 = get_all_equipped(id, game).iter().map(|e| e.defense_bonus).sum();
+        base_defense + bonus
+    }
+    
+    pub fn max_hp(&self, id: usize, game: &Game) -> i32 {
+        let base_max_hp = self.fighter.map_or(0, |f| f.base_max_hp);
+        let bonus: i32 = get_all_equipped(id, game).iter().map(|e| e.max_hp_bonus).sum();
+        base_max_hp + bonus
+    }
+    
+    // Equip object and show a message about it. Light sources are lit when equipped.
+    pub fn equip(&mut self, messages: &mut Messages) {
+        if self.item.is_none() {
+            messages.add(format!("Can't equip {} because it's not an Item.", self.name), COLOR_MSG_BAD);
+            return;
+        };
+        if let Some(ref mut equipment) = self.equipment {
+            if !equipment.equipped {
+                equipment.equipped = true;
+                messages.add(format!("Equipped {} on {}.", self.name, equipment.slot), COLOR_MSG_GOOD);
+            }
+        } else {
+            messages.add(format!("Can't equip {} because it's not an Equipment.", self.name), COLOR_MSG_BAD);
+            return;
+        }
+        if self.light_intensity > 0 && self.light_fuel != Some(0) {
+            self.light_source.0 = true;
+        }
+    }
+    
+    // Dequip object and show a message about it. Light sources are put out when dequipped.
+    pub fn dequip(&mut self, messages: &mut Messages) {
+        if self.item.is_none() {
+            messages.add(format!("Can't dequip {} because it's not an Item.", self.name), COLOR_MSG_BAD);
+            return;
+        };
+        if let Some(ref mut equipment) = self.equipment {
+            if equipment.equipped {
+                equipment.equipped = false;
+                messages.add(format!("Dequipped {} from {}.", self.name, equipment.slot), COLOR_MSG_ALERT);
+            }
+        } else {
+            messages.add(format!("Can't dequip {} because it's not an Equipment.", self.name), COLOR_MSG_BAD);
+            return;
+        }
+        self.light_source.0 = false;
+    }
+    
+    // Name of the object, including the state of its light source and where it is worn, if it is
+    // an item that has either.
+    pub fn describe(&self) -> String {
+        let mut description = self.name.clone();
+        if self.item.is_some() {
+            match self.light_fuel {
+                Some(fuel) if self.light_source.0 => description.push_str(&format!(" (lit, {} turns left)", fuel)),
+                Some(0) => description.push_str(" (burnt out)"),
+                Some(fuel) => description.push_str(&format!(" ({} turns of fuel)", fuel)),
+                None => {},
+            }
+        }
+        if let Some(equipment) = self.equipment {
+            if equipment.equipped {
+                description.push_str(&format!(" (on {})", equipment.slot));
+            }
+        }
+        description
+    }
+    
+    // Draw object in chosen terminal, as the given glyph (its own, or its sprite in a tileset).
+    pub fn draw(&self, con: &mut dyn Console, glyph: char, colorizer: fn((f64, f64, f64)) -> Color, luminance: f64) {
+        let mut color = self.hsl;
+        color.2 = luminance;
+        con.set_default_foreground(colorizer(color));
+        con.put_char(self.x, self.y, glyph, BackgroundFlag::None);
+    }
+    
+    // Erase object in chosen terminal.
+    pub fn clear(&self, con: &mut dyn Console) {
+        con.put_char(self.x, self.y, ' ', BackgroundFlag::None);
+    }
+}
+
+// The player's memory of an object, as it was when last seen: drawn dimmed where it was, while
+// that place is out of view.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Ghost {
+    pub x: i32,
+    pub y: i32,
+    pub char: char,
+    pub name: String,
+    pub hsl: (f64, f64, f64),
+    pub item: bool,                 // Whether it was an item, for the overview to mark.
+}
+
+impl Ghost {
+    pub fn of(object: &Object) -> Ghost {
+        Ghost { x: object.x, y: object.y, char: object.char, name: object.name.clone(), hsl: object.hsl, item: object.item.is_some() }
+    }
+    
+    // Draw the ghost in the chosen terminal, washed out and at a fixed lightness, so that it can be
+    // told apart from what is actually in view.
+    pub fn draw(&self, con: &mut dyn Console, glyph: char, colorizer: fn((f64, f64, f64)) -> Color, luminance: f64) {
+        let color = (self.hsl.0, self.hsl.1 * 0.5, luminance);
+        con.set_default_foreground(colorizer(color));
+        con.put_char(self.x, self.y, glyph, BackgroundFlag::None);
+    }
+    
+    pub fn clear(&self, con: &mut dyn Console) {
+        con.put_char(self.x, self.y, ' ', BackgroundFlag::None);
+    }
+}
+
+// Combat-related properties and methods (monster, player, NPC).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fighter {
+    pub base_max_hp: i32,
+    pub hp: i32,
+    pub max_mana: i32,
+    pub mana: i32,
+    pub base_defense: i32,
+    pub base_power: i32,
+    pub on_death: DeathCallback,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeathCallback {
+    Player,
+    Monster,
+}
+
+impl DeathCallback {
+    pub fn callback(self, object: &mut Object, messages: &mut Messages) {
+        use entities::DeathCallback::*;
+        let callback: fn(&mut Object, &mut Messages) = match self {
+            Player => player_death,
+            Monster => monster_death,
+        };
+        callback(object, messages);
+    }
+}
+
+fn player_death(player: &mut Object, messages: &mut Messages) {
+    // The game ended!
+    messages.add("You died!", COLOR_MSG_BAD);
+    
+    // For added effect, transform the player into a corpse!
+    player.char = '%';
+    player.hsl = COLOR_CORPSE;
+}
+
+fn monster_death(monster: &mut Object, messages: &mut Messages) {
+    // Transform it into a nasty corpse! It doesn't block, can't be attacked and doesn't move.
+    messages.add(format!("{} is dead!", monster.name), COLOR_MSG_DEATH);
+    monster.char = '%';
+    monster.hsl = COLOR_CORPSE;
+    monster.blocks = false;
+    monster.fighter = None;
+    monster.ai = None;
+    monster.name = format!("remains of {}", monster.name);
+}
+
+// Monster behaviours.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Ai {
+    Basic,      // Approach and attack the player when they can see them.
+    Wander,     // Amble about at random.
+    Confused {  // Stumble about at random, then revert to the previous behaviour.
+        previous_ai: Box<Ai>,
+        num_turns: i32,
+    },
+}
+
+// Item kinds.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Item {
+    Torch,
+    Lantern,
+    Heal,
+    Lightning,
+    Confuse,
+    Fireball,
+    Equipment,
+}
+
+// An object that can be equipped, yielding bonuses.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Equipment {
+    pub slot: Slot,
+    pub equipped: bool,
+    pub max_hp_bonus: i32,
+    pub power_bonus: i32,
+    pub defense_bonus: i32,
+}
+
+// Equipment slots. Only one item may be equipped in each slot at a time.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Slot {
+    Weapon,
+    Armour,
+    OffHand,
+    Amulet,
+}
+
+impl std::fmt::Display for Slot {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Slot::Weapon => write!(f, "weapon hand"),
+            Slot::Armour => write!(f, "body"),
+            Slot::OffHand => write!(f, "off-hand"),
+            Slot::Amulet => write!(f, "neck"),
+        }
+    }
+}
@@ -0,0 +1,623 @@
+// Field of view: which tiles can be seen from where, worked out directly from the map's tiles, so
+// that it needs no window (monsters, replays and tools can all use it). Several algorithms are
+// offered, which differ in how walls and corners cast their shadows; the one used is set in the
+// configuration file, and can be changed on the options screen.
+
+use std::cmp;
+use std::collections::HashMap;
+
+use config::*;
+use entities::*;
+use game::*;
+use lighting::*;
+use map::*;
+
+// The FOV algorithms, by the names used in the configuration file.
+pub const FOV_ALGORITHMS: &'static [(&'static str, FovAlgorithm)] = &[
+    ("Shadowcasting", FovAlgorithm::Shadowcasting),
+    ("Permissive", FovAlgorithm::Permissive),
+    ("Diamond", FovAlgorithm::Diamond),
+    ("Raycasting", FovAlgorithm::Raycasting),
+];
+
+// Tile coordinates are scaled by this much when testing lines between points inside tiles, so
+// that they can be tested exactly. Tile (x, y) covers (x, y) * SUBTILE +/- SUBTILE / 2.
+const SUBTILE: i64 = 10;
+
+// Points in a tile that lines are drawn from and to: its centre, and its corners (Permissive) or
+// points just inside the corners of its diamond (Diamond).
+const SQUARE_POINTS: [(i64, i64); 5] = [(0, 0), (-5, -5), (5, -5), (-5, 5), (5, 5)];
+const DIAMOND_POINTS: [(i64, i64); 5] = [(0, 0), (0, -4), (4, 0), (0, 4), (-4, 0)];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FovAlgorithm {
+    Shadowcasting,  // Symmetric shadowcasting: a floor tile is seen when its centre is in view, and
+                    // a wall when any part of it is. Anything you can see can see you.
+    Permissive,     // A tile is seen if a line from the centre or a corner of the viewer's tile to
+                    // the centre or a corner of it is clear, so corners are seen round generously.
+                    // Symmetric.
+    Diamond,        // Walls block as diamonds rather than squares, so they cast narrower shadows,
+                    // and gaps between diagonal walls can be seen through.
+    Raycasting,     // Rays cast from the viewer to the edge of its view, stopping at walls. Cheap,
+                    // but neither symmetric nor free of gaps.
+}
+
+impl FovAlgorithm {
+    pub fn name(self) -> &'static str {
+        FOV_ALGORITHMS.iter().find(|&&(_, algorithm)| algorithm == self)
+            .map(|&(name, _)| name)
+            .expect("every FOV algorithm is listed in FOV_ALGORITHMS")
+    }
+
+    // The next algorithm, for cycling through them on the options screen.
+    pub fn next(self) -> FovAlgorithm {
+        let i = FOV_ALGORITHMS.iter().position(|&(_, algorithm)| algorithm == self).unwrap();
+        FOV_ALGORITHMS[(i + 1) % FOV_ALGORITHMS.len()].1
+    }
+}
+
+// Which tiles of a map are in view from a viewpoint, as worked out by 'compute_fov'.
+pub struct FovMap {
+    width: i32,
+    height: i32,
+    visible: Vec<bool>,
+    stale: bool,    // Whether the map has changed in a way that could change what is in view.
+}
+
+impl FovMap {
+    pub fn new(width: i32, height: i32) -> FovMap {
+        FovMap { width: width, height: height, visible: vec![false; (width * height) as usize], stale: true }
+    }
+    
+    // Whether the view needs recomputing, because the map has changed since it was computed.
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    // Work out which tiles of 'map' can be seen from (x, y), out to 'radius' tiles away (0 for no
+    // limit). Tiles that block sight are only ever in view if 'light_walls' is set; otherwise
+    // just what lies between them is.
+    pub fn compute_fov(&mut self, map: &Map, x: i32, y: i32, radius: i32, light_walls: bool, algorithm: FovAlgorithm) {
+        assert_eq!(map_size(map), (self.width, self.height), "the FOV map must be the size of the map");
+        for visible in self.visible.iter_mut() {
+            *visible = false;
+        }
+        match algorithm {
+            FovAlgorithm::Shadowcasting => self.shadowcast(map, x, y, radius, light_walls),
+            FovAlgorithm::Permissive => self.line_of_sight(map, x, y, radius, light_walls, &SQUARE_POINTS, &SQUARE_POINTS, permissive_line),
+            FovAlgorithm::Diamond => {
+                self.line_of_sight(map, x, y, radius, light_walls, &[(0, 0)], &DIAMOND_POINTS, diamond_line);
+                if light_walls {
+                    self.reveal_walls_beside_floor(map, x, y, radius);
+                }
+            }
+            FovAlgorithm::Raycasting => {
+                self.raycast(map, x, y, radius, light_walls);
+                if light_walls {
+                    self.reveal_walls_beside_floor(map, x, y, radius);
+                }
+            }
+        }
+        self.reveal(map, x, y, true);
+        self.stale = false;
+    }
+
+    pub fn is_in_fov(&self, x: i32, y: i32) -> bool {
+        x >= 0 && x < self.width && y >= 0 && y < self.height && self.visible[(x * self.height + y) as usize]
+    }
+
+    fn reveal(&mut self, map: &Map, x: i32, y: i32, light_walls: bool) {
+        if x >= 0 && x < self.width && y >= 0 && y < self.height && (light_walls || !opaque(map, x, y)) {
+            self.visible[(x * self.height + y) as usize] = true;
+        }
+    }
+
+    // Symmetric shadowcasting, after Albert Ford. Each quadrant is scanned row by row outwards
+    // from the viewer, narrowing (or splitting) the range of slopes still in view as walls are
+    // found. Slopes are kept as exact fractions, so that the result is exactly symmetric.
+    fn shadowcast(&mut self, map: &Map, ox: i32, oy: i32, radius: i32, light_walls: bool) {
+        let (map_width, map_height) = map_size(map);
+        let max_depth = if radius > 0 { radius } else { cmp::max(map_width, map_height) };
+
+        for quadrant in 0..4 {
+            // Turn a row depth and column within this quadrant into map coordinates.
+            let transform = |depth: i32, col: i32| match quadrant {
+                0 => (ox + col, oy - depth),
+                1 => (ox + depth, oy + col),
+                2 => (ox + col, oy + depth),
+                _ => (ox - depth, oy + col),
+            };
+            let mut rows = vec![Row { depth: 1, start: (-1, 1), end: (1, 1) }];
+            while let Some(mut row) = rows.pop() {
+                if row.depth > max_depth {
+                    continue;
+                }
+                let mut previous_wall = None;
+                for col in row.min_col()..row.max_col() + 1 {
+                    let (x, y) = transform(row.depth, col);
+                    let wall = opaque(map, x, y);
+                    if (wall || row.is_symmetric(col)) && in_radius(x - ox, y - oy, radius) {
+                        self.reveal(map, x, y, light_walls);
+                    }
+                    if previous_wall == Some(true) && !wall {
+                        row.start = slope(row.depth, col);
+                    }
+                    if previous_wall == Some(false) && wall {
+                        rows.push(Row { depth: row.depth + 1, start: row.start, end: slope(row.depth, col) });
+                    }
+                    previous_wall = Some(wall);
+                }
+                if previous_wall == Some(false) {
+                    rows.push(Row { depth: row.depth + 1, start: row.start, end: row.end });
+                }
+            }
+        }
+    }
+
+    // Test every tile in range for a clear line from one of the viewer's points to one of its
+    // points. 'clear' says whether the line between two points (in SUBTILE units) is clear.
+    fn line_of_sight(&mut self, map: &Map, ox: i32, oy: i32, radius: i32, light_walls: bool,
+                     from_points: &[(i64, i64)], to_points: &[(i64, i64)],
+                     clear: fn(&Map, (i64, i64), (i64, i64), (i32, i32), (i32, i32)) -> bool) {
+        let (map_width, map_height) = map_size(map);
+        let (x_range, y_range) = if radius > 0 {
+            ((cmp::max(0, ox - radius), cmp::min(map_width, ox + radius + 1)),
+             (cmp::max(0, oy - radius), cmp::min(map_height, oy + radius + 1)))
+        } else {
+            ((0, map_width), (0, map_height))
+        };
+        for x in x_range.0..x_range.1 {
+            for y in y_range.0..y_range.1 {
+                if !in_radius(x - ox, y - oy, radius) {
+                    continue;
+                }
+                let seen = from_points.iter().any(|&(fx, fy)| to_points.iter().any(|&(tx, ty)| {
+                    let from = (ox as i64 * SUBTILE + fx, oy as i64 * SUBTILE + fy);
+                    let to = (x as i64 * SUBTILE + tx, y as i64 * SUBTILE + ty);
+                    clear(map, from, to, (ox, oy), (x, y))
+                }));
+                if seen {
+                    self.reveal(map, x, y, light_walls);
+                }
+            }
+        }
+    }
+
+    // Show the walls next to floor in view on their side nearest the viewer, which rays, or lines to
+    // the diamonds of walls meeting at a corner, can miss.
+    fn reveal_walls_beside_floor(&mut self, map: &Map, ox: i32, oy: i32, radius: i32) {
+        let (map_width, map_height) = map_size(map);
+        let mut walls = vec![];
+        for x in 0..map_width {
+            for y in 0..map_height {
+                if !opaque(map, x, y) || self.is_in_fov(x, y) || !in_radius(x - ox, y - oy, radius) {
+                    continue;
+                }
+                let (step_x, step_y) = ((ox - x).signum(), (oy - y).signum());
+                let beside_floor = [(step_x, step_y), (step_x, 0), (0, step_y)].iter().any(|&(dx, dy)| {
+                    (dx, dy) != (0, 0) && self.is_in_fov(x + dx, y + dy) && !opaque(map, x + dx, y + dy)
+                });
+                if beside_floor {
+                    walls.push((x, y));
+                }
+            }
+        }
+        for (x, y) in walls {
+            self.reveal(map, x, y, true);
+        }
+    }
+
+    // Cast a ray to every tile on the edge of the viewer's range, revealing tiles along it until
+    // it reaches a wall.
+    fn raycast(&mut self, map: &Map, ox: i32, oy: i32, radius: i32, light_walls: bool) {
+        let (map_width, map_height) = map_size(map);
+        let (x_min, x_max, y_min, y_max) = if radius > 0 {
+            (ox - radius, ox + radius, oy - radius, oy + radius)
+        } else {
+            (0, map_width - 1, 0, map_height - 1)
+        };
+        let mut ends = vec![];
+        for x in x_min..x_max + 1 {
+            ends.push((x, y_min));
+            ends.push((x, y_max));
+        }
+        for y in y_min + 1..y_max {
+            ends.push((x_min, y));
+            ends.push((x_max, y));
+        }
+        for (end_x, end_y) in ends {
+            for (x, y) in bresenham(ox, oy, end_x, end_y).into_iter().skip(1) {
+                if !in_radius(x - ox, y - oy, radius) {
+                    break;
+                }
+                self.reveal(map, x, y, light_walls);
+                if opaque(map, x, y) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl MapListener for FovMap {
+    fn map_changed(&mut self, _map: &Map, change: &MapChange) {
+        if change.sight_changed() {
+            self.stale = true;
+        }
+    }
+}
+
+// What each monster can see, each from where it stands, so that monsters notice the player with
+// their own eyes rather than by the player seeing them. Views are kept by object id and worked out
+// only when asked for: again if the monster has moved, the map has changed what can be seen, or
+// the FOV algorithm has been changed.
+#[derive(Default)]
+pub struct ActorViews {
+    views: HashMap<usize, ((i32, i32), FovMap)>,
+    algorithm: Option<FovAlgorithm>,    // What the views were worked out with.
+}
+
+impl ActorViews {
+    // Whether object 'id' can see (x, y). Monsters see as far as the player's torch reaches, and
+    // with the same algorithm as the player.
+    pub fn can_see(&mut self, id: usize, objects: &[Object], x: i32, y: i32, map: &Map, config: &FovConfig) -> bool {
+        let (viewer_x, viewer_y) = objects[id].pos();
+        let radius = config.torch_radius;
+        let (dx, dy) = (x - viewer_x, y - viewer_y);
+        if radius > 0 && dx * dx + dy * dy > radius * radius {
+            // Out of range, so there's no need to look.
+            return false;
+        }
+        if self.algorithm != Some(config.algorithm()) {
+            self.views.clear();
+            self.algorithm = Some(config.algorithm());
+        }
+        let (map_width, map_height) = map_size(map);
+        let view = self.views.entry(id).or_insert_with(|| ((viewer_x, viewer_y), FovMap::new(map_width, map_height)));
+        if view.0 != (viewer_x, viewer_y) || view.1.is_stale() {
+            view.1.compute_fov(map, viewer_x, viewer_y, radius, config.light_walls, config.algorithm());
+            view.0 = (viewer_x, viewer_y);
+        }
+        view.1.is_in_fov(x, y)
+    }
+    
+    // Object 'id' has been taken out of the objects list, moving every object after it down one
+    // place. Keep each view with the monster it belongs to.
+    pub fn object_removed(&mut self, id: usize) {
+        self.views = self.views.drain()
+            .filter(|&(view_id, _)| view_id != id)
+            .map(|(view_id, view)| (if view_id > id { view_id - 1 } else { view_id }, view))
+            .collect();
+    }
+}
+
+impl MapListener for ActorViews {
+    fn map_changed(&mut self, map: &Map, change: &MapChange) {
+        for view in self.views.values_mut() {
+            view.1.map_changed(map, change);
+        }
+    }
+}
+
+// A row of a quadrant being shadowcast: its distance from the viewer, and the range of slopes
+// (as fractions, with positive denominators) still in view.
+struct Row {
+    depth: i32,
+    start: (i32, i32),
+    end: (i32, i32),
+}
+
+impl Row {
+    // The columns from the one whose centre is nearest the start slope to the one nearest the
+    // end slope, rounding the start up and the end down when exactly between two.
+    fn min_col(&self) -> i32 {
+        floor_div((2 * self.depth * self.start.0 + self.start.1) as i64, (2 * self.start.1) as i64) as i32
+    }
+
+    fn max_col(&self) -> i32 {
+        -floor_div((self.end.1 - 2 * self.depth * self.end.0) as i64, (2 * self.end.1) as i64) as i32
+    }
+
+    // Whether the centre of the tile in a column lies within the row's slopes.
+    fn is_symmetric(&self, col: i32) -> bool {
+        col * self.start.1 >= self.depth * self.start.0 && col * self.end.1 <= self.depth * self.end.0
+    }
+}
+
+// The slope of the left edge of a tile in a row.
+fn slope(depth: i32, col: i32) -> (i32, i32) {
+    (2 * col - 1, 2 * depth)
+}
+
+// Tiles off the edge of the map block sight.
+fn opaque(map: &Map, x: i32, y: i32) -> bool {
+    let (map_width, map_height) = map_size(map);
+    x < 0 || x >= map_width || y < 0 || y >= map_height || map[x as usize][y as usize].block_sight
+}
+
+fn in_radius(dx: i32, dy: i32, radius: i32) -> bool {
+    radius <= 0 || dx * dx + dy * dy <= radius * radius
+}
+
+fn floor_div(a: i64, b: i64) -> i64 {
+    let quotient = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) { quotient - 1 } else { quotient }
+}
+
+// Call 'f' with the tiles the line between two points (in SUBTILE units) passes through, until it
+// returns false, and return whether it never did. Each call is given either a tile whose inside
+// the line passes through, or the two tiles it squeezes between: running along the edge they
+// share, or through the corner where they meet diagonally. The line is blocked if every tile in
+// any one call is.
+fn tiles_passed<F: FnMut(&[(i32, i32)]) -> bool>(from: (i64, i64), to: (i64, i64), mut f: F) -> bool {
+    let half = SUBTILE / 2;
+    let (from, to) = if from.0 <= to.0 { (from, to) } else { (to, from) };
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let tile = |n: i64| floor_div(n + half, SUBTILE) as i32;
+    let on_edge = |n: i64| (n + half) % SUBTILE == 0;
+
+    // Straight along the edge between two columns.
+    if dx == 0 && on_edge(from.0) {
+        let (low, high) = (cmp::min(from.1, to.1), cmp::max(from.1, to.1));
+        let col = tile(from.0);
+        for row in tile(low + 1)..tile(high - 1) + 1 {
+            if !f(&[(col - 1, row), (col, row)]) {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    for col in floor_div(from.0 + half, SUBTILE)..floor_div(to.0 + half - 1, SUBTILE) + 1 {
+        // The line's y range over this column, as fractions over 'den'.
+        let (low, high, den) = if dx == 0 {
+            (cmp::min(from.1, to.1), cmp::max(from.1, to.1), 1)
+        } else {
+            let x0 = cmp::max(from.0, col * SUBTILE - half);
+            let x1 = cmp::min(to.0, col * SUBTILE + half);
+            let y0 = from.1 * dx + (x0 - from.0) * dy;
+            let y1 = from.1 * dx + (x1 - from.0) * dy;
+            (cmp::min(y0, y1), cmp::max(y0, y1), dx)
+        };
+        if dy == 0 && on_edge(from.1) {
+            // Straight along the edge between two rows.
+            let row = tile(from.1);
+            if !f(&[(col as i32, row - 1), (col as i32, row)]) {
+                return false;
+            }
+            continue;
+        }
+        // The rows whose insides overlap that range.
+        let first_row = floor_div(low - half * den, SUBTILE * den) + 1;
+        let last_row = -floor_div(-(high + half * den), SUBTILE * den) - 1;
+        for row in first_row..last_row + 1 {
+            if !f(&[(col as i32, row as i32)]) {
+                return false;
+            }
+        }
+    }
+
+    // Corners the line passes diagonally through, between its ends.
+    if dx != 0 && dy != 0 {
+        let step_y = dy.signum();
+        let first_edge = floor_div(from.0 + half, SUBTILE) + 1;
+        let last_edge = -floor_div(-(to.0 + half), SUBTILE) - 1;
+        for edge in first_edge..last_edge + 1 {
+            let x = edge * SUBTILE - half;
+            let y = from.1 * dx + (x - from.0) * dy;
+            if y % dx == 0 && on_edge(y / dx) {
+                let (col, row) = (edge as i32, tile(y / dx));
+                // The tiles beside the line as it goes through the corner: ahead of it in x but
+                // behind it in y, and the other way around.
+                let (ahead, behind) = if step_y > 0 { ((col, row - 1), (col - 1, row)) } else { ((col, row), (col - 1, row - 1)) };
+                if !f(&[ahead, behind]) {
+                    return false;
+                }
+            }
+        }
+    }
+    true
+}
+
+// A line is clear for Permissive FOV if it doesn't pass through or between tiles that block
+// sight, apart from the tiles at either end.
+fn permissive_line(map: &Map, from: (i64, i64), to: (i64, i64), from_tile: (i32, i32), to_tile: (i32, i32)) -> bool {
+    tiles_passed(from, to, |tiles| {
+        tiles.iter().any(|&(x, y)| (x, y) == from_tile || (x, y) == to_tile || !opaque(map, x, y))
+    })
+}
+
+// A line is clear for Diamond FOV if it passes through the diamond inside no tile that blocks
+// sight, apart from the tiles at either end. The gaps between the diamonds of walls side by side
+// are filled in (with another diamond, around the middle of the edge they share), so that a
+// straight wall has no holes in it.
+fn diamond_line(map: &Map, from: (i64, i64), to: (i64, i64), from_tile: (i32, i32), to_tile: (i32, i32)) -> bool {
+    tiles_passed(from, to, |tiles| {
+        tiles.iter().all(|&(x, y)| {
+            if (x, y) == from_tile || (x, y) == to_tile || !opaque(map, x, y) {
+                return true;
+            }
+            let centre = (x as i64 * SUBTILE, y as i64 * SUBTILE);
+            !crosses_diamond(from, to, centre) && ![(1, 0), (-1, 0), (0, 1), (0, -1)].iter().any(|&(dx, dy)| {
+                opaque(map, x + dx, y + dy) &&
+                    crosses_diamond(from, to, (centre.0 + dx as i64 * SUBTILE / 2, centre.1 + dy as i64 * SUBTILE / 2))
+            })
+        })
+    })
+}
+
+// Whether the line between two points passes through the inside of the diamond around 'centre'
+// (the points less than half a tile from it, walking along the axes). Distance from the centre
+// along the line is convex, so is smallest at an end, or where the line crosses one of the axes.
+fn crosses_diamond(from: (i64, i64), to: (i64, i64), centre: (i64, i64)) -> bool {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    // Points along the line, as fractions 'n / d' of the way from 'from' to 'to'.
+    let points = [(0, 1), (1, 1), (centre.0 - from.0, dx), (centre.1 - from.1, dy)];
+    points.iter().any(|&(n, d)| {
+        let (n, d) = if d < 0 { (-n, -d) } else { (n, d) };
+        if d == 0 || n < 0 || n > d {
+            return false;
+        }
+        let x = from.0 * d + n * dx - centre.0 * d;
+        let y = from.1 * d + n * dy - centre.1 * d;
+        x.abs() + y.abs() < SUBTILE / 2 * d
+    })
+}
+
+// The tiles on a line between two tiles, starting with the first.
+pub fn bresenham(x0: i32, y0: i32, x1: i32, y1: i32) -> Vec<(i32, i32)> {
+    let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+    let (step_x, step_y) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+    let (mut x, mut y, mut error) = (x0, y0, dx + dy);
+    let mut tiles = vec![(x, y)];
+    while (x, y) != (x1, y1) {
+        let double_error = 2 * error;
+        if double_error >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if double_error <= dx {
+            error += dx;
+            y += step_y;
+        }
+        tiles.push((x, y));
+    }
+    tiles
+}
+
+// Recompute the player's fov, mark everything in it as explored, and remember the objects in it as
+// they are now, forgetting whatever was remembered there before.
+pub fn recompute_fov(fov_map: &mut FovMap, game: &mut Game, objects: &[Object]) {
+    let player = &objects[PLAYER];
+    let fov = &game.config.fov;
+    fov_map.compute_fov(&game.map, player.x, player.y, fov.torch_radius, fov.light_walls, fov.algorithm());
+    for y in 0..game.config.map.height {
+        for x in 0..game.config.map.width {
+            if fov_map.is_in_fov(x, y) {
+                game.map.explore(x, y);
+            }
+        }
+    }
+    game.remembered.retain(|ghost| !fov_map.is_in_fov(ghost.x, ghost.y));
+    game.remembered.extend(objects.iter().enumerate()
+        .filter(|&(id, object)| id != PLAYER && object.char != ' ' && fov_map.is_in_fov(object.x, object.y))
+        .map(|(_, object)| Ghost::of(object)));
+}
+
+// Setup field of view map for the map, and compute the player's initial fov. This is done as play
+// starts, when everything worked out from the map is new, so the tiles explored here are not
+// passed on as changes.
+pub fn initialise_fov(game: &mut Game, objects: &mut [Object]) -> FovMap {
+    let (map_width, map_height) = map_size(&game.map);
+    let mut fov_map = FovMap::new(map_width, map_height);
+    update_player_light(game, objects);
+    recompute_fov(&mut fov_map, game, objects);
+    game.map.forget_changes();
+    fov_map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // Two rooms of floor, side by side, with a closed door between them at (5, 2).
+    fn rooms_with_door() -> Map {
+        let mut map = Map::new(11, 5, Tile::wall());
+        for x in 1..10 {
+            for y in 1..4 {
+                if x != 5 {
+                    map.set_tile(x, y, Tile::empty());
+                }
+            }
+        }
+        map.set_tile(5, 2, Tile::door(false));
+        map.forget_changes();
+        map
+    }
+    
+    #[test]
+    fn fov_is_stale_after_a_change_to_sight() {
+        let mut map = rooms_with_door();
+        let mut fov_map = FovMap::new(11, 5);
+        fov_map.compute_fov(&map, 2, 2, 0, true, FovAlgorithm::Shadowcasting);
+        assert!(!fov_map.is_stale());
+        assert!(!fov_map.is_in_fov(8, 2));
+        
+        map.open(5, 2);
+        map.notify(&mut [&mut fov_map]);
+        assert!(fov_map.is_stale());
+        fov_map.compute_fov(&map, 2, 2, 0, true, FovAlgorithm::Shadowcasting);
+        assert!(!fov_map.is_stale());
+        assert!(fov_map.is_in_fov(8, 2));
+    }
+    
+    // Exploring a tile, or a change that can't alter what is in view, leaves the FOV as it was.
+    #[test]
+    fn fov_is_kept_after_other_changes() {
+        let mut map = rooms_with_door();
+        let mut fov_map = FovMap::new(11, 5);
+        fov_map.compute_fov(&map, 2, 2, 0, true, FovAlgorithm::Shadowcasting);
+        map.explore(8, 2);
+        map.set_tile(1, 1, Tile::door(true));
+        map.notify(&mut [&mut fov_map]);
+        assert!(!fov_map.is_stale());
+    }
+    
+    #[test]
+    fn monster_views_follow_changes_to_the_map() {
+        let mut map = rooms_with_door();
+        let monster = Object::new(8, 2, 'o', "orc", (0.0, 0.0, 1.0), true, (false, 0, (0, 0, 0)));
+        let objects = vec![monster];
+        let config = FovConfig::default();
+        let mut views = ActorViews::default();
+        assert!(!views.can_see(0, &objects, 2, 2, &map, &config));
+        
+        map.open(5, 2);
+        map.notify(&mut [&mut views]);
+        assert!(views.can_see(0, &objects, 2, 2, &map, &config));
+        
+        map.close(5, 2);
+        map.notify(&mut [&mut views]);
+        assert!(!views.can_see(0, &objects, 2, 2, &map, &config));
+    }
+    
+    // When an object is taken out of the list, the views of those after it move down with them.
+    #[test]
+    fn monster_views_follow_removed_objects() {
+        let map = rooms_with_door();
+        let config = FovConfig::default();
+        let mut objects: Vec<Object> = [(2, 2), (3, 3), (8, 2)].iter()
+            .map(|&(x, y)| Object::new(x, y, 'o', "orc", (0.0, 0.0, 1.0), true, (false, 0, (0, 0, 0))))
+            .collect();
+        let mut views = ActorViews::default();
+        for id in 0..3 {
+            views.can_see(id, &objects, 1, 1, &map, &config);
+        }
+        
+        objects.remove(1);
+        views.object_removed(1);
+        let mut kept: Vec<(usize, (i32, i32))> = views.views.iter().map(|(&id, view)| (id, view.0)).collect();
+        kept.sort();
+        assert_eq!(kept, vec![(0, (2, 2)), (1, (8, 2))]);
+    }
+    
+    // Changing the FOV algorithm (on the options screen) throws away the views worked out with the
+    // old one.
+    #[test]
+    fn monster_views_follow_the_fov_algorithm() {
+        let map = rooms_with_door();
+        let mut config = FovConfig::default();
+        let objects: Vec<Object> = [(2, 2), (8, 2)].iter()
+            .map(|&(x, y)| Object::new(x, y, 'o', "orc", (0.0, 0.0, 1.0), true, (false, 0, (0, 0, 0))))
+            .collect();
+        let mut views = ActorViews::default();
+        views.can_see(0, &objects, 1, 1, &map, &config);
+        views.can_see(1, &objects, 9, 1, &map, &config);
+        assert_eq!(views.views.len(), 2);
+        
+        config.algorithm = config.algorithm().next().name().into();
+        views.can_see(0, &objects, 1, 1, &map, &config);
+        assert_eq!(views.views.len(), 1);
+        assert_eq!(views.algorithm, Some(config.algorithm()));
+    }
+}
@@ -0,0 +1,596 @@
+// The game state, and the turn loop that plays commands against it.
+
+use std::cmp;
+
+use rand::*;
+
+use ai::*;
+use config::*;
+use content::*;
+use entities::*;
+use fov::*;
+use generation::*;
+use items::*;
+use lighting::*;
+use map::*;
+use replay::*;
+
+const MESSAGE_HISTORY: usize = 500;     // Older messages are forgotten.
+
+// Message colours.
+pub const COLOR_MSG_INFO: (f64, f64, f64) = (0.0, 0.0, 0.9);
+pub const COLOR_MSG_GOOD: (f64, f64, f64) = (120.0, 0.8, 0.6);
+pub const COLOR_MSG_BAD: (f64, f64, f64) = (0.0, 0.9, 0.6);
+pub const COLOR_MSG_DEATH: (f64, f64, f64) = (30.0, 1.0, 0.5);
+pub const COLOR_MSG_MAGIC: (f64, f64, f64) = (270.0, 0.9, 0.75);
+pub const COLOR_MSG_ALERT: (f64, f64, f64) = (60.0, 1.0, 0.6);
+
+// Turn scheduler constants.
+//
+// Every living actor gains 'speed' energy per clock tick, and may act once it has banked at least
+// ENERGY_THRESHOLD. Each action then deducts its time cost from the actor's energy, so an actor
+// with twice the speed gets to act twice as often. Light sources (flicker, fuel) are updated once
+// every TICKS_PER_TURN ticks, ie - once per action of a NORMAL_SPEED actor.
+const ENERGY_THRESHOLD: i32 = 100;
+pub const NORMAL_SPEED: i32 = 10;
+const TICKS_PER_TURN: i32 = 10;
+pub const COST_MOVE: i32 = 100;
+pub const COST_ATTACK: i32 = 100;
+pub const COST_WAIT: i32 = 50;
+pub const COST_ITEM: i32 = 100;             // Picking up, dropping or using an item.
+pub const COST_DOOR: i32 = 100;             // Opening or closing a door.
+
+// Define the game 'Clock', which counts elapsed game time in scheduler ticks.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Clock {
+    pub ticks: i32,
+}
+
+impl Clock {
+    // Elapsed game time in turns, ie - actions of a NORMAL_SPEED actor.
+    pub fn turns(&self) -> i32 {
+        self.ticks / TICKS_PER_TURN
+    }
+}
+
+// Define the game's random number generator.
+//
+// A simple xorshift* generator, used for all game logic in place of the thread RNG so that its
+// state can be saved along with the rest of the game.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameRng {
+    pub state: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        // The state of an xorshift generator must never be zero.
+        GameRng { state: if seed == 0 { 0x9E3779B97F4A7C15 } else { seed } }
+    }
+}
+
+impl Rng for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+    
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+}
+
+// The game state, bundled together so it can be passed around (and saved) as one.
+#[derive(Serialize, Deserialize)]
+pub struct Game {
+    pub map: Map,
+    pub inventory: Vec<Object>,     // Carried items are kept here rather than in the objects list.
+    pub flashes: Vec<Object>,       // Short-lived, invisible light sources (spell effects etc).
+    pub clock: Clock,
+    pub depth: i32,                 // Dungeon level, starting at 1.
+    pub rng: GameRng,
+    pub replay: Option<ReplayLog>,  // Every command since the game began, None if not known in full.
+    pub messages: Messages,
+    pub remembered: Vec<Ghost>,     // Objects as the player last saw them.
+    #[serde(skip)]
+    pub config: Config,             // Not saved; the configuration the game is being played with.
+    #[serde(skip)]
+    pub views: ActorViews,          // Not saved; what each monster can see, worked out as needed.
+    #[serde(skip)]
+    pub cues: Vec<Cue>,             // Not saved; what has happened since the effects layer last looked.
+}
+
+// The message log, oldest first. Colours are HSL, like everything else that is drawn.
+#[derive(Serialize, Deserialize)]
+pub struct Messages {
+    pub messages: Vec<(String, (f64, f64, f64))>,
+}
+
+impl Default for Messages {
+    fn default() -> Messages {
+        Messages::new()
+    }
+}
+
+impl Messages {
+    pub fn new() -> Self {
+        Messages { messages: vec![] }
+    }
+    
+    // Add a message, forgetting the oldest once there are more than MESSAGE_HISTORY.
+    pub fn add<T: Into<String>>(&mut self, message: T, colour: (f64, f64, f64)) {
+        self.messages.push((message.into(), colour));
+        if self.messages.len() > MESSAGE_HISTORY {
+            self.messages.remove(0);
+        }
+    }
+}
+
+// Things that happen during a turn that the effects layer may show, in the order they happened.
+// Game logic only reports them, and never reads them back, so games play the same whether or not
+// they are shown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cue {
+    Wound(i32, i32),                            // Something was hurt here.
+    Death(i32, i32),
+    Lightning((i32, i32), (i32, i32)),          // A bolt, from and to.
+    Fireball((i32, i32), (i32, i32), i32),      // Thrown from and to, exploding with this radius.
+}
+
+// Game commands. Everything the player does that changes the game state is one of these, which
+// are executed by 'take_turn'. Interactive choices (menu letters, targets) are resolved into the
+// command beforehand, so a game can be reproduced exactly from its seed and command list.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    Move(i32, i32),                             // Move, or attack whatever is in the way.
+    Wait,
+    PickUp,
+    UseItem(usize, Option<(i32, i32)>),         // Inventory index and target tile, if any.
+    DropItem(usize),
+    OpenDoor(i32, i32),                         // Open the door in this direction.
+    CloseDoor(i32, i32),                        // Close the door in this direction.
+}
+
+// Move object by dx, dy.
+pub fn move_by(id: usize, dx: i32, dy: i32, map: &Map, rule: CornerRule, objects: &mut [Object]) {
+    let (x, y) = objects[id].pos();
+    if !is_blocked(x + dx, y + dy, map, objects) && can_pass_corner(x, y, dx, dy, map, rule) {
+        objects[id].set_pos(x + dx, y + dy);
+    }
+}
+
+// Move object one step towards a target position.
+pub fn move_towards(id: usize, target_x: i32, target_y: i32, map: &Map, rule: CornerRule, objects: &mut [Object]) {
+    // Vector from this object to the target, and distance.
+    let dx = target_x - objects[id].x;
+    let dy = target_y - objects[id].y;
+    let distance = ((dx.pow(2) + dy.pow(2)) as f32).sqrt();
+    
+    // Normalize it to length 1 (preserving direction), then round it and
+    // convert to integer so the movement is restricted to the map grid.
+    let dx = (dx as f32 / distance).round() as i32;
+    let dy = (dy as f32 / distance).round() as i32;
+    move_by(id, dx, dy, map, rule, objects);
+}
+
+// Move the player, or attack whatever is in the way. Returns the time cost of the action taken,
+// which is zero if the player just walked into a wall.
+fn player_move_or_attack(dx: i32, dy: i32, game: &mut Game, objects: &mut [Object]) -> i32 {
+    // Moving nowhere is waiting (and the player can't attack themselves).
+    if (dx, dy) == (0, 0) {
+        return COST_WAIT;
+    }
+    
+    // The co-ordinates the player is moving to/attacking.
+    let x = objects[PLAYER].x + dx;
+    let y = objects[PLAYER].y + dy;
+    
+    // Try to find an attackable object there. Attacks can't reach past wall corners that moves
+    // can't get past.
+    let target_id = objects.iter().position(|object| object.fighter.is_some() && object.pos() == (x, y));
+    let rule = game.config.map.corner_rule();
+    let can_reach = can_pass_corner(objects[PLAYER].x, objects[PLAYER].y, dx, dy, &game.map, rule);
+    
+    // Attack if target found, move otherwise.
+    match target_id {
+        Some(target_id) if can_reach => {
+            let (player, target) = mut_two(PLAYER, target_id, objects);
+            player.attack(PLAYER, target, target_id, game);
+            COST_ATTACK
+        }
+        _ if can_reach && game.map.open(x, y) => {
+            // Walking into a closed door opens it.
+            game.messages.add("You open the door.", COLOR_MSG_INFO);
+            COST_DOOR
+        }
+        _ => {
+            if is_blocked(x, y, &game.map, objects) || !can_reach {
+                0
+            } else {
+                move_by(PLAYER, dx, dy, &game.map, rule, objects);
+                COST_MOVE
+            }
+        }
+    }
+}
+
+// Open ('open' true) or close the door next to the player in direction (dx, dy). Doors can't be
+// reached past wall corners, and can't be closed on anything standing in the doorway.
+fn player_open_or_close(dx: i32, dy: i32, open: bool, game: &mut Game, objects: &[Object]) -> i32 {
+    let (player_x, player_y) = objects[PLAYER].pos();
+    let (x, y) = (player_x + dx, player_y + dy);
+    let (map_width, map_height) = map_size(&game.map);
+    if dx.abs() > 1 || dy.abs() > 1 || x < 0 || x >= map_width || y < 0 || y >= map_height ||
+        !can_pass_corner(player_x, player_y, dx, dy, &game.map, game.config.map.corner_rule()) {
+        return 0;
+    }
+    if open {
+        if game.map.open(x, y) {
+            game.messages.add("You open the door.", COLOR_MSG_INFO);
+            return COST_DOOR;
+        }
+        game.messages.add("There is no closed door there.", COLOR_MSG_INFO);
+        return 0;
+    }
+    let tile = game.map[x as usize][y as usize];
+    if !tile.door || tile.blocked {
+        game.messages.add("There is no open door there.", COLOR_MSG_INFO);
+        return 0;
+    }
+    if let Some(object) = objects.iter().find(|object| object.pos() == (x, y)) {
+        game.messages.add(format!("The {} is in the way.", object.name), COLOR_MSG_INFO);
+        return 0;
+    }
+    game.map.close(x, y);
+    game.messages.add("You close the door.", COLOR_MSG_INFO);
+    COST_DOOR
+}
+
+// Mutably borrow two *separate* elements from the given slice.
+// Panics when the indexes are equal or out of bounds.
+pub fn mut_two<T>(first_index: usize, second_index: usize, items: &mut [T]) -> (&mut T, &mut T) {
+    assert!(first_index != second_index);
+    let split_at_index = cmp::max(first_index, second_index);
+    let (first_slice, second_slice) = items.split_at_mut(split_at_index);
+    if first_index < second_index {
+        (&mut first_slice[first_index], &mut second_slice[0])
+    } else {
+        (&mut second_slice[0], &mut first_slice[second_index])
+    }
+}
+
+// The game is won once every hostile monster on the level is dead.
+pub fn is_victory(objects: &[Object]) -> bool {
+    !objects.iter().any(|object| object.alive && object.ai.as_ref().is_some_and(is_hostile))
+}
+
+// Turn scheduler.
+//
+// Advance the game clock tick by tick, granting every living actor energy according to its
+// speed, until the player has banked enough energy to act again. Any monster that reaches the
+// threshold along the way takes its turn and pays the cost of the action it performed. Light
+// sources are updated on the same clock, once per turn.
+fn advance_time(game: &mut Game, objects: &mut [Object]) {
+    while objects[PLAYER].alive && objects[PLAYER].energy < ENERGY_THRESHOLD {
+        game.clock.ticks += 1;
+        
+        for object in objects.iter_mut() {
+            if object.alive {
+                object.energy += object.speed;
+            }
+        }
+        
+        for id in 0..objects.len() {
+            if id != PLAYER && objects[id].ai.is_some() && objects[id].energy >= ENERGY_THRESHOLD {
+                let cost = ai_take_turn(id, game, objects);
+                objects[id].energy -= cost;
+            }
+        }
+        
+        if game.clock.ticks % TICKS_PER_TURN == 0 {
+            for object in objects.iter_mut().chain(game.flashes.iter_mut()) {
+                object.tick_light(&mut game.rng);
+            }
+            for item in game.inventory.iter_mut() {
+                if item.tick_light(&mut game.rng) {
+                    game.messages.add(format!("Your {} goes out.", item.name), COLOR_MSG_ALERT);
+                }
+            }
+            // Spent flashes are gone for good.
+            game.flashes.retain(|flash| flash.light_source.0);
+        }
+    }
+}
+
+// Carry out a player command, then let everyone else act until the player is ready to act again.
+// Returns the time cost of the command; if it is zero, no time has passed.
+//
+// This is the only way the game state changes in response to the player, so a game can be
+// replayed by feeding the same commands back through it. Changes to the map made during the turn
+// are passed on to the FOV map and then to 'listeners' (the lighting and travel caches) at its end.
+pub fn take_turn(command: Command, game: &mut Game, objects: &mut Vec<Object>, fov_map: &mut FovMap, listeners: &mut [&mut dyn MapListener]) -> i32 {
+    if let Some(ref mut replay) = game.replay {
+        replay.commands.push(command);
+    }
+    
+    let cost = match command {
+        Command::Move(dx, dy) => player_move_or_attack(dx, dy, game, objects),
+        Command::Wait => COST_WAIT,
+        Command::PickUp => {
+            let item_id = objects.iter().position(|object| object.pos() == objects[PLAYER].pos() && object.item.is_some());
+            match item_id {
+                Some(item_id) => pick_item_up(item_id, game, objects),
+                None => 0,
+            }
+        }
+        Command::UseItem(inventory_id, target) if inventory_id < game.inventory.len() => {
+            use_item(inventory_id, target, game, objects, fov_map)
+        }
+        Command::DropItem(inventory_id) if inventory_id < game.inventory.len() => {
+            drop_item(inventory_id, game, objects)
+        }
+        Command::UseItem(..) | Command::DropItem(..) => 0,
+        Command::OpenDoor(dx, dy) => player_open_or_close(dx, dy, true, game, objects),
+        Command::CloseDoor(dx, dy) => player_open_or_close(dx, dy, false, game, objects),
+    };
+    
+    // Monsters must see any change the player made to the map before they act, and the player
+    // any they made before the fov is recomputed.
+    notify_map_changes(game, fov_map, listeners);
+    
+    // Lights and monster positions change as time passes, so the fov is recomputed whenever any
+    // time has elapsed, or the map has changed what can be seen.
+    objects[PLAYER].energy -= cost;
+    advance_time(game, objects);
+    update_player_light(game, objects);
+    notify_map_changes(game, fov_map, listeners);
+    if cost > 0 || fov_map.is_stale() {
+        recompute_fov(fov_map, game, objects);
+    }
+    
+    // And everything must hear of the tiles the player has now explored.
+    notify_map_changes(game, fov_map, listeners);
+    cost
+}
+
+// Pass changes to the map on to the player's FOV map, the monsters' views, and 'listeners'.
+fn notify_map_changes(game: &mut Game, fov_map: &mut FovMap, listeners: &mut [&mut dyn MapListener]) {
+    let mut all_listeners: Vec<&mut dyn MapListener> = vec![fov_map, &mut game.views];
+    all_listeners.extend(listeners.iter_mut().map(|listener| &mut **listener as &mut dyn MapListener));
+    game.map.notify(&mut all_listeners);
+}
+
+// Where to put one of the player's starting companions: the free floor tile nearest to 'spot'
+// that the player can walk to, or None if there isn't one. Spots off the map, or in the walls, are
+// moved to the nearest floor.
+fn companion_spot(spot: (i32, i32), map: &Map, rule: CornerRule, objects: &[Object]) -> Option<(i32, i32)> {
+    let (map_width, map_height) = map_size(map);
+    let mut tiles: Vec<(i32, i32)> = (0..map_width)
+        .flat_map(|x| (0..map_height).map(move |y| (x, y)))
+        .filter(|&(x, y)| {
+            let tile = &map[x as usize][y as usize];
+            !tile.blocked && !tile.door && !objects.iter().any(|object| object.pos() == (x, y))
+        })
+        .collect();
+    tiles.sort_by_key(|&(x, y)| cmp::max((x - spot.0).abs(), (y - spot.1).abs()));
+    tiles.into_iter().find(|&to| find_path(objects[PLAYER].pos(), to, map, rule, objects, |_, _| true).is_some())
+}
+
+// Start a new game: generate the first level and populate it, returning the game state and the
+// objects list.
+pub fn new_game(content: &Content, config: &Config, seed: u64) -> (Game, Vec<Object>) {
+    // Instantiate the player and put them in the objects list. Their position is set when the map
+    // is generated. They start with a full energy bank so that they get the first move.
+    let mut player = Object::new(0, 0, '@', "player", COLOR_PLAYER, true, (false, 0, (0, 0, 0)));
+    player.alive = true;
+    player.energy = ENERGY_THRESHOLD;
+    player.fighter = Some(Fighter { base_max_hp: 30, hp: 30, max_mana: 20, mana: 20, base_defense: 2, base_power: 5, on_death: DeathCallback::Player });
+    let mut objects = vec![player];
+    
+    // Instantiate the game state, generating a map (this also places the monsters and items). All
+    // randomness in the game comes from the seeded game RNG, so the same seed and commands always
+    // give the same game.
+    let mut rng = GameRng::new(seed);
+    let map = make_map(&mut objects, content, &config.map, 1, &mut rng);
+    let mut game = Game {
+        map: map,
+        inventory: vec![],
+        flashes: vec![],
+        clock: Clock { ticks: 0 },
+        depth: 1,
+        rng: rng,
+        replay: Some(ReplayLog { seed: seed, config: config.clone(), commands: vec![] }),
+        messages: Messages::new(),
+        remembered: vec![],
+        config: config.clone(),
+        views: Default::default(),
+        cues: vec![],
+    };
+    let (player_x, player_y) = objects[PLAYER].pos();
+    
+    // Initial equipment: a lantern, lit and held in the off-hand.
+    let mut lantern = content.item("lantern").spawn(player_x, player_y);
+    lantern.equip(&mut game.messages);
+    game.inventory.push(lantern);
+    
+    // Instantiate 'npc' and 'light bulb' objects and put them in the objects list, on the free floor
+    // nearest to where they are meant to go.
+    let npc_spot = (config.map.width / 2 - 5, config.map.height / 2);
+    if let Some((x, y)) = companion_spot(npc_spot, &game.map, config.map.corner_rule(), &objects) {
+        let npc = content.monster("cat buddy").spawn(x, y);
+        objects.push(npc);
+    }
+    let light_bulb_spots = [("flickering light bulb", (player_x + 3, player_y + 3)), ("failing light bulb", (player_x - 3, player_y - 3))];
+    for &(name, spot) in &light_bulb_spots {
+        if let Some((x, y)) = companion_spot(spot, &game.map, config.map.corner_rule(), &objects) {
+            let light_bulb = content.feature(name).spawn(x, y);
+            objects.push(light_bulb);
+        }
+    }
+    
+    // A warm welcoming message!
+    game.messages.add("Welcome stranger! Prepare to perish in the Tombs of the Ancient Kings.", COLOR_MSG_ALERT);
+    
+    (game, objects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // The objects placed with the player at the start: the cat buddy and the light bulbs.
+    fn companions(objects: &[Object]) -> Vec<&Object> {
+        objects.iter()
+            .filter(|object| object.name == "cat buddy" || object.name.ends_with("light bulb"))
+            .collect()
+    }
+    
+    fn assert_on_free_floor(objects: &[Object], game: &Game) {
+        for companion in companions(objects) {
+            let (x, y) = companion.pos();
+            let tile = &game.map[x as usize][y as usize];
+            assert!(!tile.blocked && !tile.door, "{} starts in a wall at {:?}", companion.name, (x, y));
+            assert_eq!(objects.iter().filter(|object| object.pos() == (x, y)).count(), 1,
+                       "{} starts on top of another object", companion.name);
+            assert!(find_path(objects[PLAYER].pos(), (x, y), &game.map, game.config.map.corner_rule(), objects, |_, _| true).is_some(),
+                    "{} starts where the player can't reach", companion.name);
+        }
+    }
+    
+    #[test]
+    fn companions_start_on_free_floor() {
+        let content = load_content(CONTENT_PATH).expect("the content file loads");
+        for seed in 0..20 {
+            let (game, objects) = new_game(&content, &Config::default(), seed);
+            assert_eq!(companions(&objects).len(), 3);
+            assert_on_free_floor(&objects, &game);
+        }
+    }
+    
+    // On a map with a single small room there may not be space for everyone; those that don't fit
+    // are left out rather than put in the walls.
+    #[test]
+    fn companions_fit_on_small_maps() {
+        let content = load_content(CONTENT_PATH).expect("the content file loads");
+        let config = Config {
+            map: MapConfig { width: 6, height: 6, room_min_size: 3, room_max_size: 3, max_rooms: 1, ..Default::default() },
+            ..Default::default()
+        };
+        for seed in 0..20 {
+            let (game, objects) = new_game(&content, &config, seed);
+            assert_on_free_floor(&objects, &game);
+        }
+    }
+    
+    // A game with a closed door just east of the player, and nothing else in the way.
+    fn game_with_door() -> (Game, Vec<Object>, FovMap) {
+        let content = load_content(CONTENT_PATH).expect("the content file loads");
+        let (mut game, mut objects) = new_game(&content, &Config::default(), 1);
+        let (x, y) = objects[PLAYER].pos();
+        game.map.set_tile(x + 1, y, Tile::door(false));
+        game.map.forget_changes();
+        let mut id = 1;
+        while id < objects.len() {
+            if objects[id].pos() == (x + 1, y) {
+                objects.remove(id);
+            } else {
+                id += 1;
+            }
+        }
+        let fov_map = initialise_fov(&mut game, &mut objects);
+        (game, objects, fov_map)
+    }
+    
+    #[test]
+    fn doors_open_and_close() {
+        let (mut game, mut objects, mut fov_map) = game_with_door();
+        let (x, y) = objects[PLAYER].pos();
+        assert_eq!(take_turn(Command::CloseDoor(1, 0), &mut game, &mut objects, &mut fov_map, &mut []), 0);
+        assert_eq!(take_turn(Command::OpenDoor(1, 0), &mut game, &mut objects, &mut fov_map, &mut []), COST_DOOR);
+        assert!(!game.map[(x + 1) as usize][y as usize].blocked);
+        assert_eq!(take_turn(Command::OpenDoor(1, 0), &mut game, &mut objects, &mut fov_map, &mut []), 0);
+        
+        assert_eq!(take_turn(Command::CloseDoor(1, 0), &mut game, &mut objects, &mut fov_map, &mut []), COST_DOOR);
+        assert!(game.map[(x + 1) as usize][y as usize].blocked);
+        assert_eq!(game.replay.as_ref().map(|replay| replay.commands.len()), Some(4));
+    }
+    
+    #[test]
+    fn doors_do_not_close_on_things_in_the_way() {
+        let (mut game, mut objects, mut fov_map) = game_with_door();
+        let (x, y) = objects[PLAYER].pos();
+        game.map.open(x + 1, y);
+        let content = load_content(CONTENT_PATH).expect("the content file loads");
+        objects.push(content.item("healing potion").spawn(x + 1, y));
+        assert_eq!(take_turn(Command::CloseDoor(1, 0), &mut game, &mut objects, &mut fov_map, &mut []), 0);
+        assert!(!game.map[(x + 1) as usize][y as usize].blocked);
+    }
+    
+    // A game on a map of solid rock, with a speed for the player and each monster, all in cells of
+    // their own where none of them can see another. The monsters only ever wait.
+    fn walled_in_game(speeds: &[i32]) -> (Game, Vec<Object>) {
+        let content = load_content(CONTENT_PATH).expect("the content file loads");
+        let (mut game, mut objects) = new_game(&content, &Config::default(), 1);
+        objects.truncate(1);
+        game.map = Map::new(game.config.map.width, game.config.map.height, Tile::wall());
+        for (id, &speed) in speeds.iter().enumerate() {
+            let (x, y) = (2 + 3 * id as i32, 2);
+            game.map.set_tile(x, y, Tile::empty());
+            if id != PLAYER {
+                let mut monster = content.monster("orc").spawn(x, y);
+                monster.energy = 0;
+                objects.push(monster);
+            }
+            objects[id].set_pos(x, y);
+            objects[id].speed = speed;
+        }
+        game.map.forget_changes();
+        (game, objects)
+    }
+    
+    // How many times each object has acted, going by the energy it has gained and what it has left:
+    // the monsters only wait, as does the player.
+    fn turns_taken(game: &Game, objects: &[Object], start_energy: &[i32]) -> Vec<i32> {
+        objects.iter().zip(start_energy)
+            .map(|(object, &start)| (start + object.speed * game.clock.ticks - object.energy) / COST_WAIT)
+            .collect()
+    }
+    
+    #[test]
+    fn actions_cost_time() {
+        let (mut game, mut objects) = walled_in_game(&[NORMAL_SPEED]);
+        let mut fov_map = initialise_fov(&mut game, &mut objects);
+        assert_eq!(take_turn(Command::Wait, &mut game, &mut objects, &mut fov_map, &mut []), COST_WAIT);
+        assert_eq!(game.clock.ticks, COST_WAIT / NORMAL_SPEED);
+        
+        // Walking into rock takes no time at all.
+        assert_eq!(take_turn(Command::Move(1, 0), &mut game, &mut objects, &mut fov_map, &mut []), 0);
+        assert_eq!(game.clock.ticks, COST_WAIT / NORMAL_SPEED);
+        assert!(objects[PLAYER].energy >= ENERGY_THRESHOLD);
+        
+        // Moving on the spot is waiting, not attacking oneself.
+        let hp = objects[PLAYER].fighter.map(|fighter| fighter.hp);
+        assert_eq!(take_turn(Command::Move(0, 0), &mut game, &mut objects, &mut fov_map, &mut []), COST_WAIT);
+        assert_eq!(objects[PLAYER].fighter.map(|fighter| fighter.hp), hp);
+    }
+    
+    #[test]
+    fn faster_actors_act_more_often() {
+        let (mut game, mut objects) = walled_in_game(&[NORMAL_SPEED, NORMAL_SPEED / 2, NORMAL_SPEED * 2]);
+        let mut fov_map = initialise_fov(&mut game, &mut objects);
+        let start_energy: Vec<i32> = objects.iter().map(|object| object.energy).collect();
+        for _ in 0..20 {
+            take_turn(Command::Wait, &mut game, &mut objects, &mut fov_map, &mut []);
+        }
+        let turns = turns_taken(&game, &objects, &start_energy);
+        assert_eq!(turns[PLAYER], 20);
+        assert!((turns[1] - 10).abs() <= 1, "the slow orc took {} turns", turns[1]);
+        assert!((turns[2] - 40).abs() <= 1, "the fast orc took {} turns", turns[2]);
+        
+        // Whoever has banked enough energy has used it.
+        assert!(objects[1..].iter().all(|monster| monster.energy < ENERGY_THRESHOLD));
+    }
+}
+
@@ -0,0 +1,227 @@
+// Dungeon generation: rooms joined by tunnels, populated from the content templates.
+
+use std::cmp;
+
+use rand::*;
+use rand::distributions::{Weighted, WeightedChoice, IndependentSample};
+
+use config::*;
+use content::*;
+use entities::*;
+use game::*;
+use map::*;
+
+const MAX_ROOM_MONSTERS: i32 = 3;
+const MAX_ROOM_ITEMS: i32 = 2;
+const DOOR_ONE_IN: i32 = 2;         // How many of the doorways into rooms get a (closed) door.
+
+// Room creation function.
+fn create_room(room: Rect, map: &mut Map) {
+    for x in (room.x1 + 1)..room.x2 {
+        for y in (room.y1 + 1)..room.y2 {
+            map.set_tile(x, y, Tile::empty());
+        }
+    }
+}
+
+// 'Horizontal' tunnel creation function.
+fn create_h_tunnel(x1: i32, x2: i32, y: i32, map: &mut Map) {
+    for x in cmp::min(x1, x2)..(cmp::max(x1, x2) + 1) {
+        map.set_tile(x, y, Tile::empty());
+    }
+}
+
+// 'Vertical' tunnel creation function.
+fn create_v_tunnel(y1: i32, y2: i32, x: i32, map: &mut Map) {
+    for y in cmp::min(y1, y2)..(cmp::max(y1, y2) + 1) {
+        map.set_tile(x, y, Tile::empty());
+    }
+}
+
+// Map creation function.
+//
+// Still to implement:
+//     - Need to detect when though we have not yet generated max_rooms rooms,
+//       there is insufficient empty space to create another room of at least 
+//       room_min_size^2 dimensions.
+
+pub fn make_map(objects: &mut Vec<Object>, content: &Content, config: &MapConfig, depth: i32, rng: &mut GameRng) -> Map {
+    // Make an empty map from empty tiles.
+    let mut map = Map::new(config.width, config.height, Tile::wall());
+    
+    let mut rooms = vec![];
+    
+    for _ in 0..config.max_rooms {
+        let w = rng.gen_range(config.room_min_size, config.room_max_size + 1);
+        let h = rng.gen_range(config.room_min_size, config.room_max_size + 1);
+        let x = rng.gen_range(0, config.width - w);
+        let y = rng.gen_range(0, config.height - h);
+        
+        let new_room = Rect::new(x, y, w, h);
+        
+        let failed = rooms.iter().any(|other_room| new_room.intersects_with(other_room));
+        
+        if !failed {
+            // In this case, there are no intersections between the proposed
+            // new room and any existing rooms, so we create it.
+            create_room(new_room, &mut map);
+            
+            // Get the centre co-ordinates of the room.
+            let (new_x, new_y) = new_room.center();
+            
+            if rooms.is_empty() {
+                // Then this is the first room, so we set the player start
+                // co-ordinates appropriately.
+                objects[PLAYER].set_pos(new_x, new_y);
+            } else {
+                // All other rooms after the first.
+                // Connect it to the previous room with a tunnel.
+                
+                // Get center co-ordinates of previous room.
+                let (prev_x, prev_y) = rooms[rooms.len() - 1].center();
+                
+                // First, pick a random boolean.
+                if rng.gen() {
+                    // Tunnel horizontally, then vertically.
+                    create_h_tunnel(prev_x, new_x, prev_y, &mut map);
+                    create_v_tunnel(prev_y, new_y, new_x, &mut map);
+                } else {
+                    create_v_tunnel(prev_y, new_y, prev_x, &mut map);
+                    create_h_tunnel(prev_x, new_x, new_y, &mut map);
+                }
+            }
+            
+            // Add some content to this room, such as monsters.
+            place_objects(new_room, &map, objects, content, depth, rng);
+            
+            rooms.push(new_room);
+        }
+    }
+    
+    place_doors(&rooms, &mut map, rng);
+    
+    // Nothing has been worked out from the map yet, so there is no one to tell how it was dug.
+    map.forget_changes();
+    map
+}
+
+// Put closed doors in some of the doorways where tunnels pass through the walls of rooms: gaps in
+// a room's wall with wall on either side of them, and floor in front and behind.
+fn place_doors(rooms: &[Rect], map: &mut Map, rng: &mut GameRng) {
+    let (map_width, map_height) = map_size(map);
+    for room in rooms {
+        for x in room.x1..(room.x2 + 1) {
+            for y in room.y1..(room.y2 + 1) {
+                let in_wall = x == room.x1 || x == room.x2 || y == room.y1 || y == room.y2;
+                if !in_wall || map[x as usize][y as usize].blocked {
+                    continue;
+                }
+                // Doors already placed don't count as wall, so that no two are ever side by side.
+                let wall = |x: i32, y: i32| {
+                    x < 0 || x >= map_width || y < 0 || y >= map_height ||
+                        (map[x as usize][y as usize].blocked && !map[x as usize][y as usize].door)
+                };
+                let across = wall(x - 1, y) && wall(x + 1, y) && !wall(x, y - 1) && !wall(x, y + 1);
+                let down = wall(x, y - 1) && wall(x, y + 1) && !wall(x - 1, y) && !wall(x + 1, y);
+                if (across || down) && rng.gen_range(0, DOOR_ONE_IN) == 0 {
+                    map.set_tile(x, y, Tile::door(false));
+                }
+            }
+        }
+    }
+}
+
+// Populate a room with monsters and items, drawn from the content templates that may appear at
+// this depth.
+fn place_objects(room: Rect, map: &Map, objects: &mut Vec<Object>, content: &Content, depth: i32, rng: &mut GameRng) {
+    // Spawn tables for this depth, weighted by template index.
+    let mut monster_chances: Vec<_> = content.monsters.iter().enumerate()
+        .filter(|&(_, t)| t.weight > 0 && t.depth.0 <= depth && depth <= t.depth.1)
+        .map(|(index, t)| Weighted { weight: t.weight, item: index })
+        .collect();
+    let mut item_chances: Vec<_> = content.items.iter().enumerate()
+        .filter(|&(_, t)| t.weight > 0 && t.depth.0 <= depth && depth <= t.depth.1)
+        .map(|(index, t)| Weighted { weight: t.weight, item: index })
+        .collect();
+    
+    if !monster_chances.is_empty() {
+        let monster_choice = WeightedChoice::new(&mut monster_chances);
+        
+        // Choose random number of monsters.
+        let num_monsters = rng.gen_range(0, MAX_ROOM_MONSTERS + 1);
+        
+        for _ in 0..num_monsters {
+            // Choose random spot for this monster.
+            let x = rng.gen_range(room.x1 + 1, room.x2);
+            let y = rng.gen_range(room.y1 + 1, room.y2);
+            
+            // Only place it if the tile is not blocked.
+            if !is_blocked(x, y, map, objects) {
+                let template = &content.monsters[monster_choice.ind_sample(rng)];
+                objects.push(template.spawn(x, y));
+            }
+        }
+    }
+    
+    if !item_chances.is_empty() {
+        let item_choice = WeightedChoice::new(&mut item_chances);
+        
+        // Choose random number of items.
+        let num_items = rng.gen_range(0, MAX_ROOM_ITEMS + 1);
+        
+        for _ in 0..num_items {
+            // Choose random spot for this item.
+            let x = rng.gen_range(room.x1 + 1, room.x2);
+            let y = rng.gen_range(room.y1 + 1, room.y2);
+            
+            // Only place it if the tile is not blocked.
+            if !is_blocked(x, y, map, objects) {
+                let template = &content.items[item_choice.ind_sample(rng)];
+                objects.push(template.spawn(x, y));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn generate(seed: u64) -> (Map, Vec<Object>) {
+        let content = load_content(CONTENT_PATH).expect("the content file loads");
+        let mut objects = vec![Object::new(0, 0, '@', "player", (0.0, 0.0, 1.0), true, (false, 0, (0, 0, 0)))];
+        let map = make_map(&mut objects, &content, &MapConfig::default(), 1, &mut GameRng::new(seed));
+        (map, objects)
+    }
+    
+    // Generated maps have closed doors, each in a gap in a wall, and never with anything in them.
+    #[test]
+    fn doors_are_placed_in_doorways() {
+        let mut doors = 0;
+        for seed in 0..10 {
+            let (map, objects) = generate(seed);
+            let (width, height) = map_size(&map);
+            let wall = |x: i32, y: i32| map[x as usize][y as usize].blocked && !map[x as usize][y as usize].door;
+            for x in 1..(width - 1) {
+                for y in 1..(height - 1) {
+                    if !map[x as usize][y as usize].door {
+                        continue;
+                    }
+                    doors += 1;
+                    assert!(map[x as usize][y as usize].blocked, "doors start closed");
+                    assert!((wall(x - 1, y) && wall(x + 1, y)) || (wall(x, y - 1) && wall(x, y + 1)),
+                            "the door at ({}, {}) is not in a wall", x, y);
+                    assert!(!objects.iter().any(|object| object.pos() == (x, y)));
+                }
+            }
+        }
+        assert!(doors > 0);
+    }
+    
+    // The same seed always makes the same map.
+    #[test]
+    fn generation_is_repeatable() {
+        let ((first, _), (second, _)) = (generate(7), generate(7));
+        assert_eq!(serde_json::to_string(&first).unwrap(), serde_json::to_string(&second).unwrap());
+    }
+}
@@ -0,0 +1,876 @@
+// Player input: key bindings, turning keys and mouse clicks into actions, and travel.
+
+use std::fs::File;
+use std::io::Read;
+
+use entities::*;
+use fov::*;
+use game::*;
+use items::*;
+use lighting::*;
+use map::*;
+use minimap::*;
+use rendering::*;
+use screenshot::*;
+
+// Key bindings file. If it is missing, the default preset is used.
+pub const KEYS_PATH: &'static str = "keys.ron";
+const DEFAULT_KEY_PRESET: &'static str = "arrows";
+pub const KEY_PRESETS: &'static [&'static str] = &["arrows", "numpad", "vi", "wasd"];
+
+// Result of handling a player keystroke.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlayerAction {
+    Act(Command),
+    DidntTakeTurn,
+    Inventory(InventoryUse),    // Open the inventory, to choose an item.
+    Exit,
+}
+
+// What an item is being chosen from the inventory for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InventoryUse {
+    Use,
+    Drop,
+}
+
+// Things a key can be bound to. These are what the player asks for, as opposed to 'Command's,
+// which are what the game does; menu and screen actions never reach the game state.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum Action {
+    Move(i32, i32),
+    Wait,
+    PickUp,
+    OpenDoor,
+    CloseDoor,
+    Inventory,
+    Drop,
+    Character,
+    Explore,
+    Look,
+    Messages,
+    Overview,
+    Help,
+    Screenshot,
+    Fullscreen,
+    Exit,
+}
+
+// Every action, in the order the key bindings screen lists them.
+pub const ACTIONS: &'static [Action] = &[
+    Action::Move(0, -1), Action::Move(1, -1), Action::Move(1, 0), Action::Move(1, 1),
+    Action::Move(0, 1), Action::Move(-1, 1), Action::Move(-1, 0), Action::Move(-1, -1),
+    Action::Wait, Action::PickUp, Action::OpenDoor, Action::CloseDoor, Action::Inventory, Action::Drop,
+    Action::Character, Action::Explore, Action::Look, Action::Messages, Action::Overview, Action::Help,
+    Action::Screenshot, Action::Fullscreen, Action::Exit,
+];
+
+impl Action {
+    pub fn describe(&self) -> String {
+        match *self {
+            Action::Move(dx, dy) => {
+                let direction = match (dx, dy) {
+                    (0, -1) => "north",
+                    (1, -1) => "north-east",
+                    (1, 0) => "east",
+                    (1, 1) => "south-east",
+                    (0, 1) => "south",
+                    (-1, 1) => "south-west",
+                    (-1, 0) => "west",
+                    _ => "north-west",
+                };
+                format!("Move or attack {}", direction)
+            }
+            Action::Wait => "Wait a moment".into(),
+            Action::PickUp => "Pick up an item".into(),
+            Action::OpenDoor => "Open a door".into(),
+            Action::CloseDoor => "Close a door".into(),
+            Action::Inventory => "Use an item".into(),
+            Action::Drop => "Drop an item".into(),
+            Action::Character => "Character screen".into(),
+            Action::Explore => "Explore automatically".into(),
+            Action::Look => "Look around".into(),
+            Action::Messages => "Message history".into(),
+            Action::Overview => "Map overview".into(),
+            Action::Help => "This help screen".into(),
+            Action::Screenshot => "Save a screenshot".into(),
+            Action::Fullscreen => "Toggle full screen".into(),
+            Action::Exit => "Save, back to the main menu".into(),
+        }
+    }
+}
+
+// The key bindings file: a preset, and any changes to it.
+#[derive(Clone, Deserialize)]
+pub struct KeyConfig {
+    pub preset: String,
+    #[serde(default)]
+    pub bindings: Vec<(String, Action)>,
+}
+
+// A key, as written in the bindings file: a single character ('k', '?'), or one of the names in
+// KEY_NAMES, optionally prefixed with "Alt+" and/or "Shift+". Shift only counts for named keys, as
+// it is already part of the character typed.
+#[derive(Clone, Debug)]
+pub struct KeySpec {
+    pub name: String,
+    pub code: tcod::input::KeyCode,
+    pub printable: char,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+const KEY_NAMES: &'static [(&'static str, tcod::input::KeyCode)] = &[
+    ("Escape", tcod::input::KeyCode::Escape),
+    ("Enter", tcod::input::KeyCode::Enter),
+    ("Tab", tcod::input::KeyCode::Tab),
+    ("Space", tcod::input::KeyCode::Spacebar),
+    ("Backspace", tcod::input::KeyCode::Backspace),
+    ("Up", tcod::input::KeyCode::Up),
+    ("Down", tcod::input::KeyCode::Down),
+    ("Left", tcod::input::KeyCode::Left),
+    ("Right", tcod::input::KeyCode::Right),
+    ("Home", tcod::input::KeyCode::Home),
+    ("End", tcod::input::KeyCode::End),
+    ("PageUp", tcod::input::KeyCode::PageUp),
+    ("PageDown", tcod::input::KeyCode::PageDown),
+    ("NumPad1", tcod::input::KeyCode::NumPad1),
+    ("NumPad2", tcod::input::KeyCode::NumPad2),
+    ("NumPad3", tcod::input::KeyCode::NumPad3),
+    ("NumPad4", tcod::input::KeyCode::NumPad4),
+    ("NumPad5", tcod::input::KeyCode::NumPad5),
+    ("NumPad6", tcod::input::KeyCode::NumPad6),
+    ("NumPad7", tcod::input::KeyCode::NumPad7),
+    ("NumPad8", tcod::input::KeyCode::NumPad8),
+    ("NumPad9", tcod::input::KeyCode::NumPad9),
+    ("F1", tcod::input::KeyCode::F1),
+    ("F12", tcod::input::KeyCode::F12),
+];
+
+impl KeySpec {
+    pub fn parse(name: &str) -> Option<KeySpec> {
+        let (mut alt, mut shift, mut key) = (false, false, name);
+        loop {
+            if key.starts_with("Alt+") {
+                alt = true;
+                key = &key[4..];
+            } else if key.starts_with("Shift+") {
+                shift = true;
+                key = &key[6..];
+            } else {
+                break;
+            }
+        }
+        let mut chars = key.chars();
+        let (code, printable) = match (chars.next(), chars.next()) {
+            (Some(c), None) => (tcod::input::KeyCode::Char, c),
+            _ => match KEY_NAMES.iter().find(|&&(n, _)| n == key) {
+                Some(&(_, code)) => (code, '\0'),
+                None => return None,
+            },
+        };
+        if shift && code == tcod::input::KeyCode::Char {
+            return None;
+        }
+        Some(KeySpec { name: name.into(), code: code, printable: printable, alt: alt, shift: shift })
+    }
+    
+    // The name of a key as pressed, as it would be written in the bindings file, or None if it is
+    // a key that can't be bound.
+    pub fn name_of(key: &tcod::input::Key) -> Option<String> {
+        let alt = if key.alt { "Alt+" } else { "" };
+        match KEY_NAMES.iter().find(|&&(_, code)| code == key.code) {
+            Some(&(name, _)) => Some(format!("{}{}{}", alt, if key.shift { "Shift+" } else { "" }, name)),
+            None if key.printable.is_ascii_graphic() => Some(format!("{}{}", alt, key.printable)),
+            None => None,
+        }
+    }
+    
+    pub fn matches(&self, key: &tcod::input::Key) -> bool {
+        let same_key = if self.code == tcod::input::KeyCode::Char {
+            key.printable == self.printable
+        } else {
+            key.code == self.code && key.shift == self.shift
+        };
+        same_key && key.alt == self.alt
+    }
+}
+
+// Bindings shared by all the presets, apart from where a preset needs the key for movement.
+const COMMON_BINDINGS: &'static [(&'static str, Action)] = &[
+    (".", Action::Wait),
+    ("g", Action::PickUp),
+    ("O", Action::OpenDoor),
+    ("C", Action::CloseDoor),
+    ("i", Action::Inventory),
+    ("d", Action::Drop),
+    ("c", Action::Character),
+    ("o", Action::Explore),
+    (";", Action::Look),
+    ("m", Action::Messages),
+    ("v", Action::Overview),
+    ("?", Action::Help),
+    ("F1", Action::Help),
+    ("F12", Action::Screenshot),
+    ("Alt+Enter", Action::Fullscreen),
+    ("Escape", Action::Exit),
+];
+
+// The movement keys of a preset, and any changes it makes to the common bindings.
+fn preset_bindings(preset: &str) -> Option<Vec<(&'static str, Action)>> {
+    use input::Action::*;
+    let movement = match preset {
+        // Shift turns each arrow 45 degrees clockwise, for the diagonals.
+        "arrows" => vec![("Up", Move(0, -1)), ("Down", Move(0, 1)), ("Left", Move(-1, 0)), ("Right", Move(1, 0)),
+                         ("Shift+Up", Move(1, -1)), ("Shift+Right", Move(1, 1)), ("Shift+Down", Move(-1, 1)), ("Shift+Left", Move(-1, -1)),
+                         ("Home", Move(-1, -1)), ("PageUp", Move(1, -1)), ("End", Move(-1, 1)), ("PageDown", Move(1, 1))],
+        "numpad" => vec![("NumPad8", Move(0, -1)), ("NumPad2", Move(0, 1)), ("NumPad4", Move(-1, 0)), ("NumPad6", Move(1, 0)),
+                         ("NumPad7", Move(-1, -1)), ("NumPad9", Move(1, -1)), ("NumPad1", Move(-1, 1)), ("NumPad3", Move(1, 1)),
+                         ("NumPad5", Wait)],
+        "vi" => vec![("k", Move(0, -1)), ("j", Move(0, 1)), ("h", Move(-1, 0)), ("l", Move(1, 0)),
+                     ("y", Move(-1, -1)), ("u", Move(1, -1)), ("b", Move(-1, 1)), ("n", Move(1, 1))],
+        // 'd' and 'c' are taken by movement, so drop and the character screen move elsewhere.
+        "wasd" => vec![("w", Move(0, -1)), ("s", Move(0, 1)), ("a", Move(-1, 0)), ("d", Move(1, 0)),
+                       ("q", Move(-1, -1)), ("e", Move(1, -1)), ("z", Move(-1, 1)), ("c", Move(1, 1)),
+                       ("x", Drop), ("Tab", Character)],
+        _ => return None,
+    };
+    let mut bindings = movement.clone();
+    for &(key, action) in COMMON_BINDINGS {
+        if !movement.iter().any(|&(k, _)| k == key) {
+            bindings.push((key, action));
+        }
+    }
+    Some(bindings)
+}
+
+// The active key bindings, in the order they are listed on the help screen, and the
+// configuration they were built from.
+pub struct KeyBindings {
+    pub config: KeyConfig,
+    pub bindings: Vec<(KeySpec, Action)>,
+}
+
+impl KeyBindings {
+    // Build the bindings from a preset and a list of changes. Each change replaces whatever the
+    // key was bound to before.
+    pub fn new(config: &KeyConfig) -> Result<KeyBindings, Vec<String>> {
+        let mut errors = vec![];
+        let preset = preset_bindings(&config.preset).unwrap_or_else(|| {
+            errors.push(format!("unknown preset '{}', expected one of arrows, numpad, vi or wasd", config.preset));
+            vec![]
+        });
+        
+        let mut bindings: Vec<(KeySpec, Action)> = preset.iter()
+            .map(|&(key, action)| (KeySpec::parse(key).expect("preset keys are valid"), action))
+            .collect();
+        for &(ref key, action) in &config.bindings {
+            if let Action::Move(dx, dy) = action {
+                if dx.abs() > 1 || dy.abs() > 1 || (dx == 0 && dy == 0) {
+                    errors.push(format!("key '{}': Move({}, {}) must be one step in one of the eight directions", key, dx, dy));
+                }
+            }
+            match KeySpec::parse(key) {
+                Some(spec) => {
+                    bindings.retain(|(bound, _)| bound.name != spec.name);
+                    bindings.push((spec, action));
+                }
+                None => errors.push(format!("unknown key '{}'", key)),
+            }
+        }
+        
+        if errors.is_empty() {
+            Ok(KeyBindings { config: config.clone(), bindings: bindings })
+        } else {
+            Err(errors)
+        }
+    }
+    
+    // The same changes on top of a different preset.
+    pub fn with_preset(&self, preset: &str) -> Result<KeyBindings, Vec<String>> {
+        KeyBindings::new(&KeyConfig { preset: preset.into(), bindings: self.config.bindings.clone() })
+    }
+    
+    // The bindings with a key bound to another action, recorded as one more change to the preset
+    // (in place of any earlier change to the same key).
+    pub fn rebind(&self, key: &str, action: Action) -> Result<KeyBindings, Vec<String>> {
+        let mut bindings: Vec<(String, Action)> = self.config.bindings.iter().filter(|&(k, _)| k != key).cloned().collect();
+        bindings.push((key.into(), action));
+        KeyBindings::new(&KeyConfig { preset: self.config.preset.clone(), bindings: bindings })
+    }
+    
+    // The names of the keys bound to an action, in binding order.
+    pub fn keys_for(&self, action: Action) -> Vec<&str> {
+        self.bindings.iter().filter(|&&(_, a)| a == action).map(|(spec, _)| spec.name.as_str()).collect()
+    }
+    
+    pub fn action(&self, key: &tcod::input::Key) -> Option<Action> {
+        self.bindings.iter().find(|&(spec, _)| spec.matches(key)).map(|&(_, action)| action)
+    }
+    
+    // Describe every bound action and its keys, one per line, in binding order.
+    pub fn help_text(&self) -> String {
+        let mut actions: Vec<(Action, Vec<&str>)> = vec![];
+        for &(ref spec, action) in &self.bindings {
+            match actions.iter().position(|&(a, _)| a == action) {
+                Some(i) => actions[i].1.push(&spec.name),
+                None => actions.push((action, vec![&spec.name])),
+            }
+        }
+        let mut text = String::from("Keys\n\n");
+        for (action, keys) in actions {
+            text.push_str(&format!("{:<28}{}\n", action.describe(), keys.join(", ")));
+        }
+        text
+    }
+}
+
+pub fn load_key_bindings(path: &str) -> Result<KeyBindings, String> {
+    let config = if std::path::Path::new(path).exists() {
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|e| format!("Could not read key bindings file '{}': {}", path, e))?;
+        ron::de::from_str(&text)
+            .map_err(|e| format!("Could not parse key bindings file '{}': {}", path, e))?
+    } else {
+        KeyConfig { preset: DEFAULT_KEY_PRESET.into(), bindings: vec![] }
+    };
+    
+    KeyBindings::new(&config).map_err(|errors| {
+        format!("Invalid key bindings file '{}':\n    {}", path, errors.join("\n    "))
+    })
+}
+
+// Player input carried between frames: where the mouse is, and any travel in progress.
+pub struct InputState {
+    pub mouse: tcod::input::Mouse,
+    pub travel: Option<Travel>,
+}
+
+// A walk to a chosen tile, or exploring, taking one step per frame until it arrives, runs out of
+// places to explore or is interrupted.
+pub struct Travel {
+    pub path: Vec<(i32, i32)>,      // Remaining steps, in order.
+    pub explore: bool,              // Whether to keep going to the nearest unexplored place.
+    pub replan: bool,               // Whether the map has changed under the path, so it must be
+                                    // planned again.
+    pub seen: Vec<usize>,           // What was in view at the last step.
+    pub doors: Vec<(i32, i32)>,     // Doors explored since the last step, which exploring stops
+                                    // for.
+    pub hp: i32,                    // The player's hit points at the last step.
+}
+
+impl MapListener for InputState {
+    // A planned path is only good while the tiles it was planned over can be walked as they could
+    // then, so a change to whether any tile can be walked through means planning it again. Doors
+    // that have just come into view are noted, for exploring to stop at.
+    fn map_changed(&mut self, _map: &Map, change: &MapChange) {
+        if let Some(ref mut travel) = self.travel {
+            if change.passability_changed() {
+                travel.replan = true;
+            }
+            if travel.explore && change.after.door && change.after.explored && !change.before.explored {
+                travel.doors.push((change.x, change.y));
+            }
+        }
+    }
+}
+
+// Input handler. Keystrokes are handled by 'handle_keys', apart from auto-explore. Clicking an
+// explored tile starts travelling there. Travel or exploring then carries on, a step at a time,
+// while no other input arrives.
+pub fn handle_input(tcod: &mut Tcod, bindings: &KeyBindings, game: &mut Game, objects: &[Object], fov_map: &FovMap, light_field: &LightField, input: &mut InputState) -> PlayerAction {
+    use tcod::input::{self, Event};
+    
+    match tcod.check_for_event(input::MOUSE | input::KEY_PRESS) {
+        Some(Event::Key(key)) => {
+            // Any keystroke interrupts travel.
+            input.travel = None;
+            if bindings.action(&key) == Some(Action::Explore) && objects[PLAYER].alive {
+                input.travel = Some(start_travel(vec![], true, objects, fov_map));
+                return PlayerAction::DidntTakeTurn;
+            }
+            handle_keys(tcod, key, bindings, game, objects, fov_map, light_field)
+        }
+        Some(Event::Mouse(mouse)) => {
+            input.mouse = mouse;
+            let (x, y) = (mouse.cx as i32, mouse.cy as i32);
+            let (map_width, map_height) = map_size(&game.map);
+            if mouse.lbutton_pressed && objects[PLAYER].alive && x >= 0 && x < map_width && y >= 0 && y < map_height {
+                input.travel = plan_travel((x, y), game, objects)
+                    .map(|path| start_travel(path, false, objects, fov_map));
+            }
+            PlayerAction::DidntTakeTurn
+        }
+        None => {
+            let command = input.travel.as_mut().and_then(|travel| travel_step(travel, game, objects, fov_map));
+            match command {
+                Some(command) => PlayerAction::Act(command),
+                None => {
+                    input.travel = None;
+                    PlayerAction::DidntTakeTurn
+                }
+            }
+        }
+    }
+}
+
+// What is in the player's FOV that travelling should stop for: monsters, or when exploring,
+// anything at all (monsters, items and features).
+fn visible_objects(fov_map: &FovMap, objects: &[Object], explore: bool) -> Vec<usize> {
+    objects.iter().enumerate()
+        .filter(|&(id, object)| id != PLAYER && fov_map.is_in_fov(object.x, object.y))
+        .filter(|&(_, object)| if explore { object.char != ' ' } else { object.alive && object.ai.is_some() })
+        .map(|(id, _)| id)
+        .collect()
+}
+
+// Plan a walk to an explored tile, through explored tiles only.
+fn plan_travel(to: (i32, i32), game: &mut Game, objects: &[Object]) -> Option<Vec<(i32, i32)>> {
+    let tile = &game.map[to.0 as usize][to.1 as usize];
+    if !tile.explored || tile.blocked || to == objects[PLAYER].pos() {
+        return None;
+    }
+    let path = find_path(objects[PLAYER].pos(), to, &game.map, game.config.map.corner_rule(), objects, |x, y| game.map[x as usize][y as usize].explored);
+    if path.is_none() {
+        game.messages.add("You don't know a way there.", COLOR_MSG_INFO);
+    }
+    path
+}
+
+// Plan a walk to the nearest explored floor tile next to an unexplored one, through explored
+// tiles only. Only unexplored floor counts: walls are only explored if the FOV lights them, so
+// a wall that stays unexplored beside explored floor is no reason to keep exploring. A closed
+// door with unexplored floor beyond it is walked into, which opens it.
+fn plan_explore(map: &Map, rule: CornerRule, objects: &[Object]) -> Option<Vec<(i32, i32)>> {
+    let (map_width, map_height) = map_size(map);
+    let explored = |x: i32, y: i32| map[x as usize][y as usize].explored;
+    let closed_door = |x: i32, y: i32| map[x as usize][y as usize].door && map[x as usize][y as usize].blocked;
+    let unexplored_beside = |x: i32, y: i32| {
+        (-1..2).any(|dx| (-1..2).any(|dy| {
+            let (x, y) = (x + dx, y + dy);
+            x >= 0 && x < map_width && y >= 0 && y < map_height && !explored(x, y) && !map[x as usize][y as usize].blocked
+        }))
+    };
+    find_path_to_nearest(objects[PLAYER].pos(), map, rule, objects, explored,
+                         |x, y| (explored(x, y) || closed_door(x, y)) && unexplored_beside(x, y))
+}
+
+fn start_travel(path: Vec<(i32, i32)>, explore: bool, objects: &[Object], fov_map: &FovMap) -> Travel {
+    Travel {
+        path: path,
+        explore: explore,
+        replan: false,
+        seen: visible_objects(fov_map, objects, explore),
+        doors: vec![],
+        hp: objects[PLAYER].fighter.map_or(0, |f| f.hp),
+    }
+}
+
+// The next step of a walk, or None if it has arrived or been interrupted: by something new coming
+// into view (when exploring, a door too), the player getting hurt, or something in the way.
+// Travelling never attacks.
+fn travel_step(travel: &mut Travel, game: &mut Game, objects: &[Object], fov_map: &FovMap) -> Option<Command> {
+    let visible = visible_objects(fov_map, objects, travel.explore);
+    if let Some(&id) = visible.iter().find(|id| !travel.seen.contains(id)) {
+        game.messages.add(format!("You see the {}, and stop.", objects[id].name), COLOR_MSG_ALERT);
+        return None;
+    }
+    travel.seen = visible;
+    if !travel.doors.is_empty() {
+        game.messages.add("You see a door, and stop.", COLOR_MSG_ALERT);
+        return None;
+    }
+    
+    let hp = objects[PLAYER].fighter.map_or(0, |f| f.hp);
+    if hp < travel.hp {
+        game.messages.add("You are hurt, and stop.", COLOR_MSG_ALERT);
+        return None;
+    }
+    travel.hp = hp;
+    
+    if travel.explore {
+        // Head for the nearest unexplored place, which changes with every step.
+        match plan_explore(&game.map, game.config.map.corner_rule(), objects) {
+            Some(path) => travel.path = path,
+            None => {
+                game.messages.add("You have explored everything you can reach.", COLOR_MSG_INFO);
+                return None;
+            }
+        }
+    } else if travel.replan {
+        // The map has changed since the path was planned; plan it again to the same place.
+        match travel.path.last().cloned().and_then(|to| plan_travel(to, game, objects)) {
+            Some(path) => travel.path = path,
+            None => return None,
+        }
+    }
+    travel.replan = false;
+    if travel.path.is_empty() {
+        return None;
+    }
+    let (x, y) = travel.path.remove(0);
+    let (dx, dy) = (x - objects[PLAYER].x, y - objects[PLAYER].y);
+    if dx.abs() > 1 || dy.abs() > 1 {
+        return None;
+    }
+    if let Some(object) = objects.iter().find(|object| object.blocks && object.pos() == (x, y)) {
+        game.messages.add(format!("The {} is in the way.", object.name), COLOR_MSG_INFO);
+        return None;
+    }
+    Some(Command::Move(dx, dy))
+}
+
+// Which way to open (or close) a door: towards the only closed (or open) door next to the player,
+// or, if there is more than one, the way the player chooses with a movement key.
+fn door_direction(tcod: &mut Tcod, bindings: &KeyBindings, game: &mut Game, objects: &[Object], open: bool) -> Option<(i32, i32)> {
+    let (x, y) = objects[PLAYER].pos();
+    let doors: Vec<(i32, i32)> = (-1..2)
+        .flat_map(|dy| (-1..2).map(move |dx| (dx, dy)))
+        .filter(|&(dx, dy)| {
+            let tile = &game.map[(x + dx) as usize][(y + dy) as usize];
+            tile.door && tile.blocked == open && can_pass_corner(x, y, dx, dy, &game.map, game.config.map.corner_rule())
+        })
+        .collect();
+    let verb = if open { "open" } else { "close" };
+    match doors.len() {
+        0 => {
+            game.messages.add(format!("There is no door to {} here.", verb), COLOR_MSG_INFO);
+            None
+        }
+        1 => Some(doors[0]),
+        _ => ask_direction(tcod, bindings, &format!("Which door do you want to {}?", verb)),
+    }
+}
+
+// Keystroke handler. Looks up the action bound to a keystroke, and turns it (and any menu or
+// targeting it leads to) into a command.
+fn handle_keys(tcod: &mut Tcod, key: tcod::input::Key, bindings: &KeyBindings, game: &mut Game, objects: &[Object], fov_map: &FovMap, light_field: &LightField) -> PlayerAction {
+    use input::PlayerAction::{Act, DidntTakeTurn};
+    
+    let player_alive = objects[PLAYER].alive;
+    
+    // Filter keystroke.
+    match (bindings.action(&key), player_alive) {
+        (Some(Action::Fullscreen), _) => {
+            // Toggle full-screen mode.
+            tcod.toggle_fullscreen();
+            DidntTakeTurn
+        }
+        (Some(Action::Exit), _) => PlayerAction::Exit,
+        
+        // Look around the map.
+        (Some(Action::Look), _) => {
+            look_mode(tcod, bindings, game, objects, fov_map, light_field);
+            DidntTakeTurn
+        }
+        
+        // Show the full message log.
+        (Some(Action::Messages), _) => {
+            message_history(tcod, bindings, &game.messages);
+            DidntTakeTurn
+        }
+        
+        // Show the whole of the map the player knows, scaled down to fit the screen.
+        (Some(Action::Overview), _) => {
+            overview(tcod, game, objects, fov_map, light_field);
+            DidntTakeTurn
+        }
+        
+        // Save the frame on screen.
+        (Some(Action::Screenshot), _) => {
+            match save_screenshot(tcod) {
+                Ok(name) => game.messages.add(format!("Screenshot saved as {}.", name), COLOR_MSG_INFO),
+                Err(e) => game.messages.add(e, COLOR_MSG_BAD),
+            }
+            DidntTakeTurn
+        }
+        
+        // Show every action and the keys bound to it.
+        (Some(Action::Help), _) => {
+            msgbox(&bindings.help_text(), HELP_SCREEN_WIDTH, tcod);
+            DidntTakeTurn
+        }
+        
+        // Movement keys.
+        (Some(Action::Move(dx, dy)), true) => Act(Command::Move(dx, dy)),
+        
+        // Wait a moment.
+        (Some(Action::Wait), true) => Act(Command::Wait),
+        
+        // Pick up an item.
+        (Some(Action::PickUp), true) => {
+            if objects.iter().any(|object| object.pos() == objects[PLAYER].pos() && object.item.is_some()) {
+                Act(Command::PickUp)
+            } else {
+                DidntTakeTurn
+            }
+        }
+        
+        // Open or close a door next to the player.
+        (Some(Action::OpenDoor), true) => match door_direction(tcod, bindings, game, objects, true) {
+            Some((dx, dy)) => Act(Command::OpenDoor(dx, dy)),
+            None => DidntTakeTurn,
+        },
+        (Some(Action::CloseDoor), true) => match door_direction(tcod, bindings, game, objects, false) {
+            Some((dx, dy)) => Act(Command::CloseDoor(dx, dy)),
+            None => DidntTakeTurn,
+        },
+        
+        // Show the inventory, to use an item.
+        (Some(Action::Inventory), true) => PlayerAction::Inventory(InventoryUse::Use),
+        
+        // Show the character screen.
+        (Some(Action::Character), true) => {
+            let player = &objects[PLAYER];
+            if let Some(fighter) = player.fighter {
+                let light = if player.light_source.0 {
+                    format!("{}", player.light_source.1)
+                } else {
+                    "none".into()
+                };
+                let mut msg = format!("Character information\n\nDungeon level: {}\nHP: {}/{}\nMana: {}/{}\nAttack: {}\nDefense: {}\nSpeed: {}\nLight: {}\n\nEquipment:\n",
+                                      game.depth, fighter.hp, player.max_hp(PLAYER, game), fighter.mana, fighter.max_mana, player.power(PLAYER, game), player.defense(PLAYER, game), player.speed, light);
+                for slot in &[Slot::Weapon, Slot::Armour, Slot::OffHand, Slot::Amulet] {
+                    let worn = get_equipped_in_slot(*slot, &game.inventory).map_or("nothing".into(), |id| game.inventory[id].describe());
+                    msg.push_str(&format!("  {}: {}\n", slot, worn));
+                }
+                msgbox(&msg, CHARACTER_SCREEN_WIDTH, tcod);
+            }
+            DidntTakeTurn
+        }
+        
+        // Show the inventory, to drop an item.
+        (Some(Action::Drop), true) => PlayerAction::Inventory(InventoryUse::Drop),
+        
+        _ => DidntTakeTurn,
+        
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // A walled-in room of floor, with the player in the middle.
+    fn room(width: i32, height: i32) -> (Map, Vec<Object>) {
+        let mut map = Map::new(width, height, Tile::empty());
+        for x in 0..width {
+            for y in 0..height {
+                if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                    map.set_tile(x, y, Tile::wall());
+                }
+            }
+        }
+        let player = Object::new(width / 2, height / 2, '@', "player", (0.0, 0.0, 1.0), true, (false, 0, (0, 0, 0)));
+        (map, vec![player])
+    }
+    
+    fn explore_floor(map: &mut Map) {
+        let (width, height) = map_size(map);
+        for x in 0..width {
+            for y in 0..height {
+                if !map[x as usize][y as usize].blocked {
+                    map.explore(x, y);
+                }
+            }
+        }
+    }
+    
+    // With walls left unexplored, as they are when the FOV doesn't light them, exploring must still
+    // end once every floor tile has been seen.
+    #[test]
+    fn explore_ends_when_all_floor_is_seen() {
+        let (mut map, objects) = room(10, 8);
+        explore_floor(&mut map);
+        assert_eq!(plan_explore(&map, CornerRule::NoSqueeze, &objects), None);
+    }
+    
+    // A gap in the wall leads somewhere unexplored, so exploring heads for it.
+    #[test]
+    fn explore_heads_for_unexplored_floor() {
+        let (mut map, objects) = room(10, 8);
+        explore_floor(&mut map);
+        map.set_tile(9, 4, Tile::empty());
+        let path = plan_explore(&map, CornerRule::NoSqueeze, &objects).expect("there is floor left to explore");
+        let &(x, y) = path.last().unwrap();
+        assert!(x == 8 && (y - 4).abs() <= 1, "exploring ended at ({}, {})", x, y);
+    }
+    
+    // A closed door with unexplored floor beyond it is walked into, to open it.
+    #[test]
+    fn explore_opens_doors() {
+        let (mut map, objects) = room(12, 8);
+        map.set_tile(9, 1, Tile::wall());
+        map.set_tile(9, 2, Tile::wall());
+        map.set_tile(9, 3, Tile::door(false));
+        map.set_tile(9, 4, Tile::wall());
+        map.set_tile(9, 5, Tile::wall());
+        map.set_tile(9, 6, Tile::wall());
+        for x in 0..9 {
+            for y in 0..8 {
+                map.explore(x, y);
+            }
+        }
+        let path = plan_explore(&map, CornerRule::NoSqueeze, &objects).expect("there is floor beyond the door");
+        assert_eq!(path.last(), Some(&(9, 3)));
+    }
+    
+    // Exploring stops when a door comes into view, as it does for a new object.
+    #[test]
+    fn explore_stops_for_new_doors() {
+        use config::*;
+        use content::*;
+        
+        let content = load_content(CONTENT_PATH).expect("the content file loads");
+        let (mut game, mut objects) = new_game(&content, &Config::default(), 1);
+        let (map, room_objects) = room(12, 8);
+        game.map = map;
+        for y in 1..7 {
+            game.map.set_tile(9, y, if y == 3 { Tile::door(false) } else { Tile::wall() });
+        }
+        objects.truncate(1);
+        objects[PLAYER].set_pos(room_objects[PLAYER].x, room_objects[PLAYER].y);
+        let fov_map = initialise_fov(&mut game, &mut objects);
+        let mut input = InputState { mouse: Default::default(), travel: Some(start_travel(vec![], true, &objects, &fov_map)) };
+        
+        // The door in view from the start is headed for, not stopped at.
+        assert!(travel_step(input.travel.as_mut().unwrap(), &mut game, &objects, &fov_map).is_some());
+        
+        // Floor coming into view is no reason to stop, but a door is.
+        game.map.explore(10, 3);
+        game.map.notify(&mut [&mut input]);
+        assert!(travel_step(input.travel.as_mut().unwrap(), &mut game, &objects, &fov_map).is_some());
+        game.map.set_tile(10, 1, Tile::door(false));
+        game.map.explore(10, 1);
+        game.map.notify(&mut [&mut input]);
+        assert_eq!(travel_step(input.travel.as_mut().unwrap(), &mut game, &objects, &fov_map), None);
+        assert_eq!(game.messages.messages.last().map(|message| message.0.as_str()), Some("You see a door, and stop."));
+    }
+    
+    #[test]
+    fn door_keys_are_bound_in_every_preset() {
+        for preset in KEY_PRESETS {
+            let bindings = KeyBindings::new(&KeyConfig { preset: preset.to_string(), bindings: vec![] }).unwrap();
+            let actions: Vec<Action> = bindings.bindings.iter().map(|&(_, action)| action).collect();
+            assert!(actions.contains(&Action::OpenDoor) && actions.contains(&Action::CloseDoor), "in preset {}", preset);
+        }
+    }
+    
+    fn key(code: tcod::input::KeyCode, printable: char, shift: bool, alt: bool) -> tcod::input::Key {
+        tcod::input::Key { code: code, printable: printable, shift: shift, alt: alt, ..Default::default() }
+    }
+    
+    #[test]
+    fn key_names_are_parsed() {
+        use tcod::input::KeyCode;
+        let spec = KeySpec::parse("k").unwrap();
+        assert_eq!((spec.code, spec.printable, spec.alt, spec.shift), (KeyCode::Char, 'k', false, false));
+        let spec = KeySpec::parse("Alt+Enter").unwrap();
+        assert_eq!((spec.code, spec.alt, spec.shift), (KeyCode::Enter, true, false));
+        let spec = KeySpec::parse("Shift+Alt+Up").unwrap();
+        assert_eq!((spec.code, spec.alt, spec.shift), (KeyCode::Up, true, true));
+        
+        // Shift is part of the character typed, so it can't be given for one; and names must be
+        // known.
+        for name in &["Shift+k", "Enterprise", "", "Alt+"] {
+            assert!(KeySpec::parse(name).is_none(), "'{}' was parsed", name);
+        }
+    }
+    
+    #[test]
+    fn keys_match_their_specs() {
+        use tcod::input::KeyCode;
+        let shift_up = KeySpec::parse("Shift+Up").unwrap();
+        assert!(shift_up.matches(&key(KeyCode::Up, '\0', true, false)));
+        assert!(!shift_up.matches(&key(KeyCode::Up, '\0', false, false)));
+        
+        // A character matches whatever key typed it, shifted or not.
+        let question = KeySpec::parse("?").unwrap();
+        assert!(question.matches(&key(KeyCode::Char, '?', true, false)));
+        assert!(!question.matches(&key(KeyCode::Char, '?', true, true)));
+    }
+    
+    #[test]
+    fn bindings_change_the_preset() {
+        use tcod::input::KeyCode;
+        let config = KeyConfig { preset: "vi".into(), bindings: vec![("g".into(), Action::Wait), ("F1".into(), Action::Look)] };
+        let bindings = KeyBindings::new(&config).unwrap();
+        assert_eq!(bindings.action(&key(KeyCode::Char, 'h', false, false)), Some(Action::Move(-1, 0)));
+        assert_eq!(bindings.action(&key(KeyCode::Char, 'g', false, false)), Some(Action::Wait));
+        assert_eq!(bindings.action(&key(KeyCode::F1, '\0', false, false)), Some(Action::Look));
+        assert_eq!(bindings.action(&key(KeyCode::Char, 'Z', true, false)), None);
+        
+        // The same changes carry over to another preset.
+        let wasd = bindings.with_preset("wasd").unwrap();
+        assert_eq!(wasd.action(&key(KeyCode::Char, 'g', false, false)), Some(Action::Wait));
+        assert_eq!(wasd.action(&key(KeyCode::Char, 'x', false, false)), Some(Action::Drop));
+        assert_eq!(wasd.action(&key(KeyCode::Char, 'd', false, false)), Some(Action::Move(1, 0)));
+    }
+    
+    #[test]
+    fn bad_bindings_are_reported() {
+        let config = KeyConfig {
+            preset: "emacs".into(),
+            bindings: vec![("Hyper+x".into(), Action::Wait), ("j".into(), Action::Move(2, 0)), ("k".into(), Action::Move(0, 0))],
+        };
+        let errors = KeyBindings::new(&config).err().expect("the bindings are refused");
+        assert_eq!(errors, vec![
+            "unknown preset 'emacs', expected one of arrows, numpad, vi or wasd".to_string(),
+            "unknown key 'Hyper+x'".to_string(),
+            "key 'j': Move(2, 0) must be one step in one of the eight directions".to_string(),
+            "key 'k': Move(0, 0) must be one step in one of the eight directions".to_string(),
+        ]);
+    }
+    
+    #[test]
+    fn pressed_keys_are_named_as_in_the_bindings_file() {
+        use tcod::input::KeyCode;
+        assert_eq!(KeySpec::name_of(&key(KeyCode::Char, '?', true, false)), Some("?".into()));
+        assert_eq!(KeySpec::name_of(&key(KeyCode::Number5, '5', false, true)), Some("Alt+5".into()));
+        assert_eq!(KeySpec::name_of(&key(KeyCode::Spacebar, ' ', false, false)), Some("Space".into()));
+        assert_eq!(KeySpec::name_of(&key(KeyCode::Up, '\0', true, true)), Some("Alt+Shift+Up".into()));
+        assert_eq!(KeySpec::name_of(&key(KeyCode::F5, '\0', false, false)), None);
+        
+        // Every name given can be read back, and matches the key it was given for.
+        for &(code, printable, shift, alt) in &[(KeyCode::Char, 'k', false, true), (KeyCode::NumPad7, '7', false, false), (KeyCode::Enter, '\r', true, false)] {
+            let pressed = key(code, printable, shift, alt);
+            let spec = KeySpec::parse(&KeySpec::name_of(&pressed).unwrap()).unwrap();
+            assert!(spec.matches(&pressed), "{:?} doesn't match", spec);
+        }
+    }
+    
+    #[test]
+    fn rebinding_a_key_changes_the_preset() {
+        use tcod::input::KeyCode;
+        let bindings = KeyBindings::new(&KeyConfig { preset: "vi".into(), bindings: vec![("g".into(), Action::Wait)] }).unwrap();
+        let bindings = bindings.rebind("g", Action::Look).unwrap().rebind("x", Action::Explore).unwrap();
+        assert_eq!(bindings.config.bindings, vec![("g".to_string(), Action::Look), ("x".to_string(), Action::Explore)]);
+        assert_eq!(bindings.action(&key(KeyCode::Char, 'g', false, false)), Some(Action::Look));
+        assert_eq!(bindings.keys_for(Action::Explore), vec!["o", "x"]);
+        assert_eq!(bindings.keys_for(Action::PickUp), Vec::<&str>::new());
+        
+        // Like any other change, it carries over to another preset.
+        let arrows = bindings.with_preset("arrows").unwrap();
+        assert_eq!(arrows.action(&key(KeyCode::Char, 'x', false, false)), Some(Action::Explore));
+    }
+    
+    #[test]
+    fn every_bound_action_is_listed() {
+        for preset in KEY_PRESETS {
+            let bindings = KeyBindings::new(&KeyConfig { preset: preset.to_string(), bindings: vec![] }).unwrap();
+            for &(_, action) in &bindings.bindings {
+                assert!(ACTIONS.contains(&action), "{:?} from preset {} isn't listed", action, preset);
+            }
+        }
+    }
+    
+    #[test]
+    fn the_shipped_bindings_load() {
+        let bindings = load_key_bindings(KEYS_PATH).unwrap();
+        assert_eq!(bindings.config.preset, DEFAULT_KEY_PRESET);
+        assert!(bindings.help_text().contains("Open a door"));
+    }
+}
+
@@ -0,0 +1,318 @@
+// Picking up, dropping and using items, and the spells they cast.
+
+use ai::*;
+use entities::*;
+use fov::*;
+use game::*;
+use lighting::*;
+
+const INVENTORY_CAPACITY: usize = 26;   // One per menu letter.
+
+// Consumables and spells.
+const HEAL_AMOUNT: i32 = 10;
+const LIGHTNING_DAMAGE: i32 = 20;
+const LIGHTNING_RANGE: i32 = 5;
+const LIGHTNING_FLASH: (i32, i32) = (3000, 1);     // Flash (intensity, turns).
+const CONFUSE_RANGE: i32 = 8;
+const CONFUSE_NUM_TURNS: i32 = 10;
+const FIREBALL_RADIUS: i32 = 3;
+const FIREBALL_DAMAGE: i32 = 12;
+const FIREBALL_FLASH: (i32, i32) = (6000, 2);
+
+// Outcome of using an item. Items that are UsedUp are removed from the inventory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UseResult {
+    UsedUp,
+    UsedAndKept,
+    Cancelled,
+}
+
+// Add to the player's inventory and remove from the map. Returns the time cost of the action. The
+// objects after it in the list keep their order, as monsters act in that order.
+pub fn pick_item_up(object_id: usize, game: &mut Game, objects: &mut Vec<Object>) -> i32 {
+    if game.inventory.len() >= INVENTORY_CAPACITY {
+        game.messages.add(format!("Your inventory is full, cannot pick up {}.", objects[object_id].name), COLOR_MSG_BAD);
+        0
+    } else {
+        let item = objects.remove(object_id);
+        game.views.object_removed(object_id);
+        game.messages.add(format!("You picked up a {}!", item.name), COLOR_MSG_GOOD);
+        game.inventory.push(item);
+        COST_ITEM
+    }
+}
+
+// Remove from the player's inventory and put it on the floor at the player's feet.
+pub fn drop_item(inventory_id: usize, game: &mut Game, objects: &mut Vec<Object>) -> i32 {
+    let mut item = game.inventory.remove(inventory_id);
+    if item.equipment.is_some() {
+        item.dequip(&mut game.messages);
+    }
+    item.set_pos(objects[PLAYER].x, objects[PLAYER].y);
+    game.messages.add(format!("You dropped a {}.", item.name), COLOR_MSG_INFO);
+    objects.push(item);
+    COST_ITEM
+}
+
+// Use an item from the inventory, dispatching on its kind. Returns the time cost of the action.
+pub fn use_item(inventory_id: usize, target: Option<(i32, i32)>, game: &mut Game, objects: &mut [Object], fov_map: &FovMap) -> i32 {
+    use entities::Item::*;
+    // Just call the "use_function" if it is defined.
+    if let Some(item) = game.inventory[inventory_id].item {
+        let on_use: fn(usize, Option<(i32, i32)>, &mut Game, &mut [Object], &FovMap) -> UseResult = match item {
+            Torch => toggle_equipment,
+            Lantern => toggle_equipment,
+            Equipment => toggle_equipment,
+            Heal => cast_heal,
+            Lightning => cast_lightning,
+            Confuse => cast_confuse,
+            Fireball => cast_fireball,
+        };
+        match on_use(inventory_id, target, game, objects, fov_map) {
+            UseResult::UsedUp => {
+                // Destroy after use, unless it was cancelled for some reason.
+                game.inventory.remove(inventory_id);
+                COST_ITEM
+            }
+            UseResult::UsedAndKept => COST_ITEM,
+            UseResult::Cancelled => {
+                game.messages.add("Cancelled", COLOR_MSG_INFO);
+                0
+            }
+        }
+    } else {
+        let message = format!("The {} cannot be used.", game.inventory[inventory_id].name);
+        game.messages.add(message, COLOR_MSG_INFO);
+        0
+    }
+}
+
+// Equip or dequip an item. Anything already equipped in the same slot is dequipped first.
+fn toggle_equipment(inventory_id: usize, _target: Option<(i32, i32)>, game: &mut Game, _objects: &mut [Object], _fov_map: &FovMap) -> UseResult {
+    let equipment = match game.inventory[inventory_id].equipment {
+        Some(equipment) => equipment,
+        None => return UseResult::Cancelled,
+    };
+    if equipment.equipped {
+        game.inventory[inventory_id].dequip(&mut game.messages);
+    } else if game.inventory[inventory_id].light_intensity > 0 && game.inventory[inventory_id].light_fuel == Some(0) {
+        let message = format!("The {} has no fuel left.", game.inventory[inventory_id].name);
+        game.messages.add(message, COLOR_MSG_BAD);
+        return UseResult::Cancelled;
+    } else {
+        // If the slot is already being used, dequip whatever is there first.
+        if let Some(current) = get_equipped_in_slot(equipment.slot, &game.inventory) {
+            game.inventory[current].dequip(&mut game.messages);
+        }
+        game.inventory[inventory_id].equip(&mut game.messages);
+    }
+    UseResult::UsedAndKept
+}
+
+// The equipment worn by the object with the given id. Only the player carries an inventory, so
+// other objects have none.
+pub fn get_all_equipped(id: usize, game: &Game) -> Vec<Equipment> {
+    if id != PLAYER {
+        return vec![];
+    }
+    game.inventory
+        .iter()
+        .filter(|item| item.equipment.is_some_and(|e| e.equipped))
+        .map(|item| item.equipment.unwrap())
+        .collect()
+}
+
+// Find the inventory index of the item equipped in a slot, if there is one.
+pub fn get_equipped_in_slot(slot: Slot, inventory: &[Object]) -> Option<usize> {
+    for (inventory_id, item) in inventory.iter().enumerate() {
+        if item.equipment.as_ref().is_some_and(|e| e.equipped && e.slot == slot) {
+            return Some(inventory_id);
+        }
+    }
+    None
+}
+
+fn cast_heal(_inventory_id: usize, _target: Option<(i32, i32)>, game: &mut Game, objects: &mut [Object], _fov_map: &FovMap) -> UseResult {
+    // Heal the player.
+    if let Some(fighter) = objects[PLAYER].fighter {
+        if fighter.hp == objects[PLAYER].max_hp(PLAYER, game) {
+            game.messages.add("You are already at full health.", COLOR_MSG_BAD);
+            return UseResult::Cancelled;
+        }
+        game.messages.add("Your wounds start to feel better!", COLOR_MSG_MAGIC);
+        objects[PLAYER].heal(PLAYER, HEAL_AMOUNT, game);
+        return UseResult::UsedUp;
+    }
+    UseResult::Cancelled
+}
+
+fn cast_lightning(_inventory_id: usize, _target: Option<(i32, i32)>, game: &mut Game, objects: &mut [Object], fov_map: &FovMap) -> UseResult {
+    // Find the closest enemy (inside a maximum range) and damage it.
+    let monster_id = closest_monster(LIGHTNING_RANGE, fov_map, objects);
+    if let Some(monster_id) = monster_id {
+        // Zap it!
+        game.messages.add(format!("A lightning bolt strikes the {} with a loud thunder! The damage is {} hit points.",
+                                  objects[monster_id].name, LIGHTNING_DAMAGE), COLOR_MSG_MAGIC);
+        let (x, y) = objects[monster_id].pos();
+        game.flashes.push(light_flash(x, y, LIGHTNING_FLASH));
+        game.cues.push(Cue::Lightning(objects[PLAYER].pos(), (x, y)));
+        objects[monster_id].take_damage(LIGHTNING_DAMAGE, &mut game.messages);
+        UseResult::UsedUp
+    } else {
+        // No enemy found within maximum range.
+        game.messages.add("No enemy is close enough to strike.", COLOR_MSG_BAD);
+        UseResult::Cancelled
+    }
+}
+
+fn cast_confuse(_inventory_id: usize, target: Option<(i32, i32)>, game: &mut Game, objects: &mut [Object], _fov_map: &FovMap) -> UseResult {
+    // Confuse the monster at the target tile, if there is one.
+    let monster_id = target.and_then(|pos| {
+        objects.iter().position(|obj| obj.pos() == pos && obj.fighter.is_some() && obj.ai.is_some())
+    });
+    if let Some(monster_id) = monster_id {
+        let old_ai = objects[monster_id].ai.take().unwrap_or(Ai::Basic);
+        // Replace the monster's AI with a "confused" one; after some turns it will restore the old AI.
+        objects[monster_id].ai = Some(Ai::Confused {
+            previous_ai: Box::new(old_ai),
+            num_turns: CONFUSE_NUM_TURNS,
+        });
+        game.messages.add(format!("The eyes of {} look vacant, as it starts to stumble around!", objects[monster_id].name), COLOR_MSG_MAGIC);
+        UseResult::UsedUp
+    } else {
+        // No enemy at the target.
+        game.messages.add("There is nothing there to confuse.", COLOR_MSG_BAD);
+        UseResult::Cancelled
+    }
+}
+
+fn cast_fireball(_inventory_id: usize, target: Option<(i32, i32)>, game: &mut Game, objects: &mut [Object], _fov_map: &FovMap) -> UseResult {
+    // Throw a fireball at the target tile.
+    let (x, y) = match target {
+        Some(tile_pos) => tile_pos,
+        None => return UseResult::Cancelled,
+    };
+    game.messages.add(format!("The fireball explodes, burning everything within {} tiles!", FIREBALL_RADIUS), COLOR_MSG_MAGIC);
+    
+    // The explosion lights up its surroundings for a moment.
+    game.flashes.push(light_flash(x, y, FIREBALL_FLASH));
+    game.cues.push(Cue::Fireball(objects[PLAYER].pos(), (x, y), FIREBALL_RADIUS));
+    
+    for obj in objects {
+        if obj.distance(x, y) <= FIREBALL_RADIUS as f32 && obj.fighter.is_some() {
+            game.messages.add(format!("The {} gets burned for {} hit points.", obj.name, FIREBALL_DAMAGE), COLOR_MSG_MAGIC);
+            obj.take_damage(FIREBALL_DAMAGE, &mut game.messages);
+        }
+    }
+    UseResult::UsedUp
+}
+
+// Find closest enemy, up to a maximum range, and in the player's FOV. Friendly monsters are never
+// picked.
+fn closest_monster(max_range: i32, fov_map: &FovMap, objects: &[Object]) -> Option<usize> {
+    let mut closest_enemy = None;
+    let mut closest_dist = (max_range + 1) as f32;  // Start with (slightly more than) maximum range.
+    
+    for (id, object) in objects.iter().enumerate() {
+        if (id != PLAYER) && object.fighter.is_some() && object.ai.as_ref().is_some_and(is_hostile) &&
+            fov_map.is_in_fov(object.x, object.y)
+        {
+            // Calculate distance between this object and the player.
+            let dist = objects[PLAYER].distance_to(object);
+            if dist < closest_dist {
+                // It's closer, so remember it.
+                closest_enemy = Some(id);
+                closest_dist = dist;
+            }
+        }
+    }
+    closest_enemy
+}
+
+// What an item has to be aimed at, if anything: the prompt to show while aiming, the maximum range
+// (if there is one), and whether there must be a monster on the chosen tile.
+pub fn item_targeting(item: &Object) -> Option<(&'static str, Option<f32>, bool)> {
+    match item.item {
+        Some(Item::Confuse) => Some(("Choose an enemy to confuse, then press Enter or click. Escape cancels.", Some(CONFUSE_RANGE as f32), true)),
+        Some(Item::Fireball) => Some(("Choose a target tile for the fireball, then press Enter or click. Escape cancels.", None, false)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::*;
+    use content::*;
+    use map::*;
+    
+    // Other objects keep their places in the list, and monsters keep their views, when an item is
+    // picked up from the middle of it.
+    #[test]
+    fn picking_up_keeps_the_objects_in_order() {
+        let content = load_content(CONTENT_PATH).expect("the content file loads");
+        let (mut game, mut objects) = new_game(&content, &Config::default(), 1);
+        let (x, y) = objects[PLAYER].pos();
+        objects.insert(1, content.item("healing potion").spawn(x, y));
+        let others: Vec<String> = objects[2..].iter().map(|object| object.name.clone()).collect();
+        let last = objects.len() - 1;
+        let (last_x, last_y) = objects[last].pos();
+        let could_see = game.views.can_see(last, &objects, last_x, last_y, &game.map, &game.config.fov);
+        
+        assert_eq!(pick_item_up(1, &mut game, &mut objects), COST_ITEM);
+        assert_eq!(game.inventory.last().map(|item| item.name.as_str()), Some("healing potion"));
+        assert_eq!(objects[1..].iter().map(|object| object.name.clone()).collect::<Vec<_>>(), others);
+        assert_eq!(game.views.can_see(last - 1, &objects, last_x, last_y, &game.map, &game.config.fov), could_see);
+    }
+    
+    #[test]
+    fn picking_up_into_a_full_inventory_fails() {
+        let content = load_content(CONTENT_PATH).expect("the content file loads");
+        let (mut game, mut objects) = new_game(&content, &Config::default(), 1);
+        let (x, y) = objects[PLAYER].pos();
+        while game.inventory.len() < INVENTORY_CAPACITY {
+            game.inventory.push(content.item("torch").spawn(x, y));
+        }
+        objects.push(content.item("healing potion").spawn(x, y));
+        let count = objects.len();
+        assert_eq!(pick_item_up(count - 1, &mut game, &mut objects), 0);
+        assert_eq!(objects.len(), count);
+    }
+    
+    // The cat buddy is nearer, but lightning goes for the orc.
+    #[test]
+    fn lightning_strikes_enemies_only() {
+        let content = load_content(CONTENT_PATH).expect("the content file loads");
+        let (mut game, mut objects) = new_game(&content, &Config::default(), 1);
+        objects.truncate(1);
+        game.map = Map::new(game.config.map.width, game.config.map.height, Tile::empty());
+        game.map.forget_changes();
+        objects[PLAYER].set_pos(10, 10);
+        objects.push(content.monster("cat buddy").spawn(11, 10));
+        objects.push(content.monster("orc").spawn(13, 10));
+        let fov_map = initialise_fov(&mut game, &mut objects);
+        assert_eq!(closest_monster(LIGHTNING_RANGE, &fov_map, &objects), Some(2));
+        
+        objects.truncate(2);
+        assert_eq!(closest_monster(LIGHTNING_RANGE, &fov_map, &objects), None);
+    }
+    
+    // Equipment bonuses go to the player alone, whatever the other objects are called.
+    #[test]
+    fn only_the_player_gets_equipment_bonuses() {
+        let content = load_content(CONTENT_PATH).expect("the content file loads");
+        let (mut game, mut objects) = new_game(&content, &Config::default(), 1);
+        let mut sword = content.item("sword").spawn(0, 0);
+        sword.equip(&mut game.messages);
+        game.inventory.push(sword);
+        let mut impostor = content.monster("orc").spawn(0, 0);
+        impostor.name = "player".into();
+        objects.push(impostor);
+        
+        let base_power = |id: usize| objects[id].fighter.map_or(0, |fighter| fighter.base_power);
+        assert!(objects[PLAYER].power(PLAYER, &game) > base_power(PLAYER));
+        let impostor = objects.len() - 1;
+        assert_eq!(objects[impostor].power(impostor, &game), base_power(impostor));
+        assert!(get_all_equipped(impostor, &game).is_empty());
+    }
+}
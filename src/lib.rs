@@ -0,0 +1,48 @@
+// A roguelike built on libtcod, in which light matters as much as monsters. The game itself lives
+// here, as a library, so that it can be tested and built upon by tools; 'main.rs' is just a thin
+// front-end that loads the configuration and content and runs it.
+
+// The code is written with explicit field names and 'static lifetimes throughout, and the drawing
+// and light functions take the game's state piece by piece; clippy's objections to both are off.
+#![allow(clippy::redundant_field_names, clippy::redundant_static_lifetimes)]
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+extern crate tcod;
+extern crate rand;
+extern crate hsl;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate ron;
+extern crate toml;
+extern crate serde_json;
+extern crate crossterm;
+
+// The dungeon and what is in it.
+pub mod map;
+pub mod generation;
+pub mod entities;
+pub mod content;
+
+// Light and sight.
+pub mod lighting;
+pub mod fov;
+
+// Playing the game.
+pub mod game;
+pub mod items;
+pub mod ai;
+pub mod input;
+
+// Presentation and persistence.
+pub mod rendering;
+pub mod palette;
+pub mod effects;
+pub mod minimap;
+pub mod screenshot;
+pub mod terminal;
+pub mod tileset;
+pub mod state;
+pub mod config;
+pub mod save;
+pub mod replay;
@@ -0,0 +1,259 @@
+// Lighting: the light cast by each light source, and how illumination is turned into the lightness
+// things are drawn with.
+
+use std::collections::{HashMap, HashSet};
+
+use entities::*;
+use game::*;
+use items::*;
+use map::*;
+
+pub type LightField = Vec<Vec<i32>>;
+
+// A light source's place, intensity and modulation (as bits, so it can be hashed): everything the
+// light it casts depends on, apart from the map.
+type LightKey = (i32, i32, i32, u64);
+
+// The light cast by each light source, as last computed, so that lights that haven't moved aren't
+// recomputed every turn. Lights are forgotten when they go unused for a pass over the lights, or
+// when a change to the map alters what they can reach.
+pub struct LightCache {
+    fields: HashMap<LightKey, (LightField, (i32, i32), (i32, i32))>,
+    used: HashSet<LightKey>,
+}
+
+impl Default for LightCache {
+    fn default() -> LightCache {
+        LightCache::new()
+    }
+}
+
+impl LightCache {
+    pub fn new() -> LightCache {
+        LightCache { fields: HashMap::new(), used: HashSet::new() }
+    }
+    
+    // The light cast by 'object', computing it if it isn't already known.
+    pub fn light_field(&mut self, map: &Map, object: &Object, modulation: f64) -> &(LightField, (i32, i32), (i32, i32)) {
+        let key = (object.x, object.y, object.light_source.1, modulation.to_bits());
+        self.used.insert(key);
+        self.fields.entry(key).or_insert_with(|| compute_lightfield(map, object, modulation))
+    }
+    
+    // Forget the lights that weren't asked for since the last call: they have moved, changed or
+    // gone out.
+    pub fn forget_unused(&mut self) {
+        let used = &self.used;
+        self.fields.retain(|key, _| used.contains(key));
+        self.used.clear();
+    }
+}
+
+impl MapListener for LightCache {
+    // Light passes through the same tiles that can be seen through, so only changes to those
+    // matter, and then only to the lights whose fields cover the changed tile.
+    fn map_changed(&mut self, _map: &Map, change: &MapChange) {
+        if change.sight_changed() {
+            self.fields.retain(|_, &mut (_, start, end)| {
+                change.x < start.0 || change.x > end.0 || change.y < start.1 || change.y > end.1
+            });
+        }
+    }
+}
+
+// Ways of turning the linear illumination into the lightness tiles and objects are drawn with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapping {
+    Exponential,    // Brightens quickly, then levels off; dim light is easy to see by.
+    Reinhard,       // Brightens more slowly, keeping more detail in bright light.
+    Linear,         // No compression at all; only strong light shows up.
+}
+
+impl ToneMapping {
+    pub fn next(self) -> ToneMapping {
+        match self {
+            ToneMapping::Exponential => ToneMapping::Reinhard,
+            ToneMapping::Reinhard => ToneMapping::Linear,
+            ToneMapping::Linear => ToneMapping::Exponential,
+        }
+    }
+}
+
+// Create a short-lived, invisible light source, of the given (intensity, turns).
+pub fn light_flash(x: i32, y: i32, flash: (i32, i32)) -> Object {
+    let mut object = Object::new(x, y, ' ', "flash", COLOR_PLAYER, false, (true, flash.0, (0, 0, 0)));
+    object.light_fuel = Some(flash.1);
+    object
+}
+
+// The player's own light comes from whatever light source they hold in their off-hand.
+pub fn update_player_light(game: &Game, objects: &mut [Object]) {
+    objects[PLAYER].light_source = get_equipped_in_slot(Slot::OffHand, &game.inventory)
+        .map_or((false, 0, (0, 0, 0)), |id| game.inventory[id].light_source);
+}
+
+// Turn a linear total illumination (0 -> 9999, ambient included) into a log lightness
+// (0.0 -> 1.0), which is what tiles and objects are drawn with.
+pub fn tone_map(illumination: i32, tone_mapping: ToneMapping) -> f64 {
+    let lin_lum_val: f64 = (illumination as f64) / 9999.0;
+    let log_lum_val: f64 = match tone_mapping {
+        ToneMapping::Exponential => {
+            //~let a = 0.08166865;
+            //~let b = 2.57916377;
+            //~let c = -0.07517411;
+            let a = -1.01179495;
+            let b = -4.47099458;
+            let c = 1.01214152;
+            (((b * lin_lum_val).exp()) * a) + c
+        }
+        ToneMapping::Reinhard => {
+            let scaled = lin_lum_val * 8.0;
+            scaled / (1.0 + scaled)
+        }
+        ToneMapping::Linear => lin_lum_val,
+    };
+    if log_lum_val > 1.0 {
+        1.0
+    } else {
+        log_lum_val
+    }
+}
+
+pub fn compute_lightfield(map: &Map, object: &Object, modulation: f64) -> (LightField, (i32, i32), (i32, i32)) {
+    let (map_width, map_height) = map_size(map);
+    
+    let float_light_intensity: f64 = object.light_source.1 as f64;
+    let float_light_radius: f64 = float_light_intensity.sqrt();
+    let int_light_radius: i32 = float_light_radius.round() as i32;
+    //println!("Int light radius {}", int_light_radius);
+    
+    let light_field_dimensions: (i32, i32) = ((2 * int_light_radius) + 1, (2 * int_light_radius) + 1);
+    let mut light_field: LightField = vec![vec![0; light_field_dimensions.0 as usize]; light_field_dimensions.1 as usize];
+    
+    let map_light_coords: (i32, i32) = (object.x, object.y);
+    //println!("Light coords {} {}", object.x, object.y);
+    
+    let map_offset_start: (i32, i32) = ((map_light_coords.0 - int_light_radius), (map_light_coords.1 - int_light_radius));
+    let map_offset_end: (i32, i32) = ((map_light_coords.0 + int_light_radius), (map_light_coords.1 + int_light_radius));
+    //println!("Map start offsets {} {}", map_offset_start.0, map_offset_start.1);
+    //println!("Map end offsets {} {}", map_offset_end.0, map_offset_end.1);
+    
+    let field_light_coords: (f64, f64) = (((map_light_coords.0 as f64) + 0.5) - (map_offset_start.0 as f64), ((map_light_coords.1 as f64) + 0.5) - (map_offset_start.1 as f64));
+    //println!("Field light coords {} {}", field_light_coords.0, field_light_coords.1);
+    
+    for map_target_y_coord in (map_offset_start.1)..(map_offset_end.1) {
+        'target_x: for map_target_x_coord in (map_offset_start.0)..(map_offset_end.0) {
+            //println!("---------------------------------------------------------");
+            
+            let field_target_coords: (f64, f64) = (((map_target_x_coord as f64) + 0.5) - (map_offset_start.0 as f64), ((map_target_y_coord as f64) + 0.5) - (map_offset_start.1 as f64));
+            //println!("Field target coords {} {}", field_target_coords.0, field_target_coords.1);
+            
+            let field_light_target_dist_comps: (f64, f64) = ((field_target_coords.0 - field_light_coords.0), (field_target_coords.1 - field_light_coords.1));
+            let field_light_target_distance: f64 = ((field_light_target_dist_comps.0).powi(2) + (field_light_target_dist_comps.1).powi(2)).sqrt();
+            //println!("Field light -> target distance {} {} -> {}", field_light_target_dist_comps.0, field_light_target_dist_comps.1, field_light_target_distance);
+            
+            let mut field_ray_coords: (f64, f64) = (field_light_coords.0, field_light_coords.1);
+            let mut field_ray_brightness: f64 = float_light_intensity;
+            
+            let field_dist_step: f64 = 0.1;
+            let field_dist_increments: f64 = field_light_target_distance / field_dist_step;
+            
+            let field_dist_step_comps: (f64, f64) = ((field_light_target_dist_comps.0 / field_dist_increments), (field_light_target_dist_comps.1 / field_dist_increments));
+            //println!("-> Step {} --> {} {}", field_dist_step, field_dist_step_comps.0, field_dist_step_comps.1);
+            //println!("-> Increments {}", field_dist_increments);
+            
+            let mut field_travelled_dist_this_target: (f64, (f64, f64)) = (0.0, (0.0, 0.0));
+            
+            for _ in 0..(field_dist_increments as i32) {
+                
+                let map_check_coords: (i32, i32) = (((field_ray_coords.0).trunc() as i32) + map_offset_start.0, ((field_ray_coords.1).trunc() as i32) + map_offset_start.1);
+                if (map_check_coords.0 < 0) || (map_check_coords.0 > (map_width - 1)) || (map_check_coords.1 < 0) || (map_check_coords.1 > (map_height - 1)) {
+                    continue 'target_x;
+                }
+                
+                let field_write_coords: (i32, i32) = ((field_ray_coords.0).trunc() as i32, (field_ray_coords.1).trunc() as i32);
+                let int_ray_brightness: i32 = field_ray_brightness.round() as i32;
+                
+                if light_field[field_write_coords.0 as usize][field_write_coords.1 as usize] < int_ray_brightness {
+                    light_field[field_write_coords.0 as usize][field_write_coords.1 as usize] = int_ray_brightness;
+                }
+                
+                if map[map_check_coords.0 as usize][map_check_coords.1 as usize].block_sight {
+                    continue 'target_x;
+                }
+                
+                field_ray_coords.0 += field_dist_step_comps.0;
+                field_ray_coords.1 += field_dist_step_comps.1;
+                
+                field_travelled_dist_this_target.0 += field_dist_step;
+                (field_travelled_dist_this_target.1).0 += field_dist_step_comps.0;
+                (field_travelled_dist_this_target.1).1 += field_dist_step_comps.1;
+                
+                // Reduce light intensity here...
+                let mut modulation_distance = field_travelled_dist_this_target.0 * modulation;
+                if modulation_distance < 1.0 {
+                    modulation_distance = 1.0;
+                }
+                field_ray_brightness = float_light_intensity / ((modulation_distance).powi(1));
+                if field_ray_brightness > float_light_intensity {
+                    field_ray_brightness = float_light_intensity;
+                }
+                // 
+            }
+            //println!("---> Travelled {} --> {} {}", field_travelled_dist_this_target.0, (field_travelled_dist_this_target.1).0, (field_travelled_dist_this_target.1).1);
+            //println!("--------> Ray final field coords {} {}", field_ray_coords.0, field_ray_coords.1);
+        }
+    }
+    
+    (light_field, map_offset_start, map_offset_end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn lamp(x: i32, y: i32) -> Object {
+        Object::new(x, y, ' ', "lamp", (0.0, 0.0, 1.0), false, (true, 25, (0, 0, 0)))
+    }
+    
+    #[test]
+    fn light_fields_are_cached() {
+        let map = Map::new(30, 10, Tile::empty());
+        let mut cache = LightCache::new();
+        let first = cache.light_field(&map, &lamp(5, 5), 0.5).clone();
+        assert_eq!(cache.fields.len(), 1);
+        assert_eq!(cache.light_field(&map, &lamp(5, 5), 0.5).0, first.0);
+        assert_eq!(cache.fields.len(), 1);
+        
+        // A light that isn't asked for in a pass is forgotten.
+        cache.forget_unused();
+        cache.light_field(&map, &lamp(6, 5), 0.5);
+        cache.forget_unused();
+        assert_eq!(cache.fields.len(), 1);
+    }
+    
+    // A change to what can be seen through forgets the lights that reach the changed tile, and
+    // only those.
+    #[test]
+    fn changes_to_sight_forget_lights_covering_them() {
+        let mut map = Map::new(30, 10, Tile::empty());
+        let mut cache = LightCache::new();
+        cache.light_field(&map, &lamp(5, 5), 0.5);
+        cache.light_field(&map, &lamp(24, 5), 0.5);
+        
+        map.set_tile(7, 5, Tile::door(false));
+        map.notify(&mut [&mut cache]);
+        assert_eq!(cache.fields.keys().map(|key| key.0).collect::<Vec<_>>(), vec![24]);
+        
+        // The light is worked out again, now blocked by the door.
+        let (field, start, _) = cache.light_field(&map, &lamp(5, 5), 0.5).clone();
+        assert_eq!(field[(9 - start.0) as usize][(5 - start.1) as usize], 0);
+        
+        // Opening a door into a room with no light in it forgets nothing.
+        map.set_tile(15, 2, Tile::door(false));
+        map.forget_changes();
+        map.open(15, 2);
+        map.notify(&mut [&mut cache]);
+        assert_eq!(cache.fields.len(), 2);
+    }
+}
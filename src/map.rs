@@ -0,0 +1,554 @@
+// The dungeon map, changes to it and the events they raise, and movement and pathfinding over it.
+
+use std::cmp;
+use std::collections::BinaryHeap;
+use std::ops::Index;
+
+use entities::*;
+
+const COLOR_DARK_WALL: (f64, f64, f64) = (120.0, 1.0, 0.196);
+pub const COLOR_DARK_GROUND: (f64, f64, f64) = (120.0, 0.333, 0.5);
+const COLOR_DOOR_CLOSED: (f64, f64, f64) = (30.0, 0.6, 0.3);
+const COLOR_DOOR_OPEN: (f64, f64, f64) = (30.0, 0.4, 0.45);
+
+// The rules for diagonal steps past wall corners, by the names used in the configuration file.
+pub const CORNER_RULES: &'static [(&'static str, CornerRule)] = &[
+    ("Allow", CornerRule::Allow),
+    ("NoSqueeze", CornerRule::NoSqueeze),
+    ("NoCutting", CornerRule::NoCutting),
+];
+
+const PATH_COST_STRAIGHT: i32 = 100;
+const PATH_COST_DIAGONAL: i32 = 101;    // A diagonal step takes as long as a straight one; this
+                                        // just keeps paths from zig-zagging for no reason.
+const PATH_COST_OBJECT: i32 = 1000;     // Extra cost of a tile with something blocking in it.
+const PATH_COST_DOOR: i32 = 100;        // Extra cost of a closed door: the move that opens it.
+
+// The map: a grid of tiles, read as map[x][y]. Tiles can only be changed through its methods,
+// each of which records the change as a MapChange, so that everything worked out from the map
+// (the field of view, lighting, planned paths and exploring) can be told about it and kept up to
+// date. It is
+// saved as just the grid; changes not yet passed on are not saved.
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Map {
+    tiles: Vec<Vec<Tile>>,
+    #[serde(skip)]
+    changes: Vec<MapChange>,
+}
+
+// A change to one tile, as it was before and after.
+#[derive(Clone, Copy, Debug)]
+pub struct MapChange {
+    pub x: i32,
+    pub y: i32,
+    pub before: Tile,
+    pub after: Tile,
+}
+
+// Anything that keeps state worked out from the map, and so needs to hear about changes to it.
+// Listeners are passed to 'Map::notify', which is called once a turn.
+pub trait MapListener {
+    fn map_changed(&mut self, map: &Map, change: &MapChange);
+}
+
+// How diagonal steps past wall corners are treated, for the player, monsters and pathfinding alike.
+// The configuration chooses one (map.corner_rule).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CornerRule {
+    Allow,          // Diagonal steps are always allowed.
+    NoSqueeze,      // No squeezing between two walls.
+    NoCutting,      // No cutting past any wall corner.
+}
+
+// Define a 'Tile' object.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Tile {
+    pub blocked: bool,
+    pub block_sight: bool,
+    pub explored: bool,
+    pub door: bool,     // Doors can be opened and closed; open, they neither block nor hide.
+    pub color_hsl: (f64, f64, f64),
+}
+
+// Define Tile object methods.
+impl Tile {
+    pub fn empty() -> Self {
+        Tile{blocked: false, block_sight: false, explored: false, door: false, color_hsl: COLOR_DARK_GROUND}
+    }
+    
+    pub fn wall() -> Self {
+        Tile{blocked: true, block_sight: true, explored: false, door: false, color_hsl: COLOR_DARK_WALL}
+    }
+    
+    pub fn door(open: bool) -> Self {
+        let color_hsl = if open { COLOR_DOOR_OPEN } else { COLOR_DOOR_CLOSED };
+        Tile{blocked: !open, block_sight: !open, explored: false, door: true, color_hsl: color_hsl}
+    }
+    
+    // What the tile is, as the player would call it.
+    pub fn name(&self) -> &'static str {
+        match (self.door, self.blocked) {
+            (true, true) => "Closed door",
+            (true, false) => "Open door",
+            (false, true) => "Wall",
+            (false, false) => "Floor",
+        }
+    }
+}
+
+impl MapChange {
+    // Whether the change affects what can be seen (and lit) through the tile.
+    pub fn sight_changed(&self) -> bool {
+        self.before.block_sight != self.after.block_sight
+    }
+    
+    // Whether the change affects whether the tile can be walked through.
+    pub fn passability_changed(&self) -> bool {
+        self.before.blocked != self.after.blocked
+    }
+}
+
+impl Map {
+    // A map of 'width' by 'height' tiles, all the same.
+    pub fn new(width: i32, height: i32, tile: Tile) -> Map {
+        Map { tiles: vec![vec![tile; height as usize]; width as usize], changes: vec![] }
+    }
+    
+    pub fn width(&self) -> i32 {
+        self.tiles.len() as i32
+    }
+    
+    pub fn height(&self) -> i32 {
+        self.tiles.first().map_or(0, |column| column.len()) as i32
+    }
+    
+    // Replace a tile. Whether it has been explored is kept, as that is the player's knowledge of
+    // it rather than part of it.
+    pub fn set_tile(&mut self, x: i32, y: i32, tile: Tile) {
+        let before = self.tiles[x as usize][y as usize];
+        let after = Tile { explored: before.explored, ..tile };
+        self.tiles[x as usize][y as usize] = after;
+        self.changes.push(MapChange { x: x, y: y, before: before, after: after });
+    }
+    
+    // Open a closed door. Returns whether there was one to open.
+    pub fn open(&mut self, x: i32, y: i32) -> bool {
+        let tile = self.tiles[x as usize][y as usize];
+        if tile.door && tile.blocked {
+            self.set_tile(x, y, Tile::door(true));
+            true
+        } else {
+            false
+        }
+    }
+    
+    // Close an open door. Returns whether there was one to close.
+    pub fn close(&mut self, x: i32, y: i32) -> bool {
+        let tile = self.tiles[x as usize][y as usize];
+        if tile.door && !tile.blocked {
+            self.set_tile(x, y, Tile::door(false));
+            true
+        } else {
+            false
+        }
+    }
+    
+    // Dig out a wall or door, leaving floor. Returns whether there was anything to dig.
+    pub fn dig(&mut self, x: i32, y: i32) -> bool {
+        let tile = self.tiles[x as usize][y as usize];
+        if tile.blocked || tile.door {
+            self.set_tile(x, y, Tile::empty());
+            true
+        } else {
+            false
+        }
+    }
+    
+    // Mark a tile as explored. This is only the player's knowledge of the map, which changes
+    // neither what can be seen nor where can be walked, but auto-explore watches for what comes
+    // into view, so a tile explored for the first time is recorded as a change like any other.
+    pub fn explore(&mut self, x: i32, y: i32) {
+        let before = self.tiles[x as usize][y as usize];
+        if !before.explored {
+            let after = Tile { explored: true, ..before };
+            self.tiles[x as usize][y as usize] = after;
+            self.changes.push(MapChange { x: x, y: y, before: before, after: after });
+        }
+    }
+    
+    // Pass on every change made since the last call to each listener, in the order they were
+    // made, then forget them.
+    pub fn notify(&mut self, listeners: &mut [&mut dyn MapListener]) {
+        let changes: Vec<MapChange> = self.changes.drain(..).collect();
+        for change in &changes {
+            for listener in listeners.iter_mut() {
+                listener.map_changed(self, change);
+            }
+        }
+    }
+    
+    // Forget the changes made so far, without passing them on. For a map that nothing has been
+    // worked out from yet, such as one just generated.
+    pub fn forget_changes(&mut self) {
+        self.changes.clear();
+    }
+}
+
+impl Index<usize> for Map {
+    type Output = [Tile];
+    
+    fn index(&self, x: usize) -> &[Tile] {
+        &self.tiles[x]
+    }
+}
+
+// Define a 'Rect' rectangular room object.
+#[derive(Clone, Copy, Debug)]
+pub struct Rect {
+    pub x1: i32,
+    pub y1: i32,
+    pub x2: i32,
+    pub y2: i32,
+}
+
+// Rect object methods.
+impl Rect {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Rect { x1: x, y1: y, x2: x + w, y2: y + h }
+    }
+    
+    pub fn center(&self) -> (i32, i32) {
+        let center_x = (self.x1 + self.x2) / 2;
+        let center_y = (self.y1 + self.y2) / 2;
+        
+        (center_x, center_y)
+    }
+    
+    pub fn intersects_with(&self, other: &Rect) -> bool {
+        // Returns true if this rectangle intersects with target.
+        (self.x1 <= other.x2) && (self.x2 >= other.x1) && (self.y1 <= self.y2) && (self.y2 >= other.y1)
+    }
+}
+
+// The width and height of a map, in tiles.
+pub fn map_size(map: &Map) -> (i32, i32) {
+    (map.width(), map.height())
+}
+
+// Returns true if the tile is a wall or is occupied by a blocking object.
+pub fn is_blocked(x: i32, y: i32, map: &Map, objects: &[Object]) -> bool {
+    // First test the map tile.
+    if map[x as usize][y as usize].blocked {
+        return true;
+    }
+    
+    // Now check for any blocking objects.
+    objects.iter().any(|object| object.blocks && object.pos() == (x, y))
+}
+
+// Whether a one-step move (or attack) from (x, y) by (dx, dy) is allowed past the wall corners
+// on either side of it, according to 'rule'. Only walls count; objects never block a diagonal.
+pub fn can_pass_corner(x: i32, y: i32, dx: i32, dy: i32, map: &Map, rule: CornerRule) -> bool {
+    if dx == 0 || dy == 0 {
+        return true;
+    }
+    let wall_beside = map[(x + dx) as usize][y as usize].blocked;
+    let wall_ahead = map[x as usize][(y + dy) as usize].blocked;
+    match rule {
+        CornerRule::Allow => true,
+        CornerRule::NoSqueeze => !(wall_beside && wall_ahead),
+        CornerRule::NoCutting => !(wall_beside || wall_ahead),
+    }
+}
+
+// Find the cheapest path from one tile to another, in steps in any of the eight directions, by
+// A* search. Returns the tiles along the path, not including the start, or None if there is no
+// way there. Walls block the path (as do tiles for which 'passable' is false), while tiles with
+// blocking objects in them are just costly, so that monsters will queue up behind each other in
+// a corridor rather than give up. Closed doors cost the extra move it takes to open them. Diagonal
+// steps past wall corners are allowed as 'rule' says.
+pub fn find_path<F>(from: (i32, i32), to: (i32, i32), map: &Map, rule: CornerRule, objects: &[Object], passable: F) -> Option<Vec<(i32, i32)>>
+    where F: Fn(i32, i32) -> bool
+{
+    let distance = |x: i32, y: i32| cmp::max((x - to.0).abs(), (y - to.1).abs()) * PATH_COST_STRAIGHT;
+    search_path(from, map, rule, objects, passable, |x, y| (x, y) == to, distance)
+}
+
+// Find the cheapest path to the nearest tile for which 'is_goal' is true, other than the start, by
+// Dijkstra search. Otherwise as 'find_path'.
+pub fn find_path_to_nearest<F, G>(from: (i32, i32), map: &Map, rule: CornerRule, objects: &[Object], passable: F, is_goal: G) -> Option<Vec<(i32, i32)>>
+    where F: Fn(i32, i32) -> bool, G: Fn(i32, i32) -> bool
+{
+    search_path(from, map, rule, objects, passable, |x, y| (x, y) != from && is_goal(x, y), |_, _| 0)
+}
+
+// The search behind 'find_path' and 'find_path_to_nearest'. 'heuristic' must never overestimate
+// the cost of the rest of the path; with a heuristic of zero, this is a Dijkstra search.
+fn search_path<F, G, H>(from: (i32, i32), map: &Map, rule: CornerRule, objects: &[Object], passable: F, is_goal: G, heuristic: H) -> Option<Vec<(i32, i32)>>
+    where F: Fn(i32, i32) -> bool, G: Fn(i32, i32) -> bool, H: Fn(i32, i32) -> i32
+{
+    let (map_width, map_height) = map_size(map);
+    let index = |x: i32, y: i32| (x * map_height + y) as usize;
+    
+    let mut occupied = vec![false; (map_width * map_height) as usize];
+    for object in objects.iter().filter(|object| object.blocks) {
+        occupied[index(object.x, object.y)] = true;
+    }
+    
+    let mut cost_so_far = vec![i32::MAX; (map_width * map_height) as usize];
+    let mut came_from = vec![None; (map_width * map_height) as usize];
+    let mut open = BinaryHeap::new();
+    cost_so_far[index(from.0, from.1)] = 0;
+    open.push((cmp::Reverse(heuristic(from.0, from.1)), from));
+    
+    while let Some((_, (x, y))) = open.pop() {
+        if is_goal(x, y) {
+            // Walk back along the path to the start.
+            let mut path = vec![];
+            let mut tile = (x, y);
+            while tile != from {
+                path.push(tile);
+                tile = came_from[index(tile.0, tile.1)].expect("every tile on the path has a predecessor");
+            }
+            path.reverse();
+            return Some(path);
+        }
+        
+        for dy in -1..2 {
+            for dx in -1..2 {
+                let (next_x, next_y) = (x + dx, y + dy);
+                if (dx == 0 && dy == 0) || next_x < 0 || next_x >= map_width || next_y < 0 || next_y >= map_height {
+                    continue;
+                }
+                let tile = &map[next_x as usize][next_y as usize];
+                if (tile.blocked && !tile.door) || !can_pass_corner(x, y, dx, dy, map, rule) {
+                    continue;
+                }
+                let goal = is_goal(next_x, next_y);
+                if !goal && !passable(next_x, next_y) {
+                    continue;
+                }
+                
+                let mut cost = cost_so_far[index(x, y)] + if dx != 0 && dy != 0 { PATH_COST_DIAGONAL } else { PATH_COST_STRAIGHT };
+                if !goal && occupied[index(next_x, next_y)] {
+                    cost += PATH_COST_OBJECT;
+                }
+                if tile.blocked {
+                    cost += PATH_COST_DOOR;
+                }
+                if cost < cost_so_far[index(next_x, next_y)] {
+                    cost_so_far[index(next_x, next_y)] = cost;
+                    came_from[index(next_x, next_y)] = Some((x, y));
+                    open.push((cmp::Reverse(cost + heuristic(next_x, next_y)), (next_x, next_y)));
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // Remembers every change it is told about.
+    #[derive(Default)]
+    struct Recorder {
+        changes: Vec<(i32, i32, bool, bool)>,
+    }
+    
+    impl MapListener for Recorder {
+        fn map_changed(&mut self, _map: &Map, change: &MapChange) {
+            self.changes.push((change.x, change.y, change.sight_changed(), change.passability_changed()));
+        }
+    }
+    
+    fn notified(map: &mut Map) -> Vec<(i32, i32, bool, bool)> {
+        let mut recorder = Recorder::default();
+        map.notify(&mut [&mut recorder]);
+        recorder.changes
+    }
+    
+    #[test]
+    fn opening_and_closing_a_door_notifies_listeners() {
+        let mut map = Map::new(5, 5, Tile::empty());
+        map.set_tile(2, 2, Tile::door(false));
+        map.forget_changes();
+        
+        assert!(map.open(2, 2));
+        assert_eq!(map[2][2].name(), "Open door");
+        assert_eq!(notified(&mut map), vec![(2, 2, true, true)]);
+        assert!(!map.open(2, 2), "an open door can't be opened again");
+        assert_eq!(notified(&mut map), vec![]);
+        
+        assert!(map.close(2, 2));
+        assert_eq!(map[2][2].name(), "Closed door");
+        assert_eq!(notified(&mut map), vec![(2, 2, true, true)]);
+        assert!(!map.close(2, 2), "a closed door can't be closed again");
+        assert!(!map.close(1, 1), "floor can't be closed");
+        assert_eq!(notified(&mut map), vec![]);
+    }
+    
+    #[test]
+    fn digging_notifies_listeners() {
+        let mut map = Map::new(5, 5, Tile::wall());
+        map.set_tile(1, 1, Tile::empty());
+        map.set_tile(3, 3, Tile::door(true));
+        map.forget_changes();
+        
+        assert!(map.dig(2, 2));
+        assert!(!map.dig(1, 1), "floor can't be dug");
+        assert!(map.dig(3, 3));
+        assert_eq!(map[2][2].name(), "Floor");
+        assert_eq!(map[3][3].name(), "Floor");
+        // Digging out an open door changes nothing that can be seen or walked through, but is
+        // still a change.
+        assert_eq!(notified(&mut map), vec![(2, 2, true, true), (3, 3, false, false)]);
+    }
+    
+    #[test]
+    fn changes_are_kept_until_notified() {
+        let mut map = Map::new(5, 5, Tile::wall());
+        map.dig(1, 1);
+        map.dig(1, 2);
+        assert_eq!(notified(&mut map).len(), 2);
+        assert_eq!(notified(&mut map), vec![]);
+    }
+    
+    // Exploring a tile is passed on the first time, as a change to neither sight nor passability.
+    #[test]
+    fn exploring_is_a_change_to_nothing_seen_or_walked() {
+        let mut map = Map::new(5, 5, Tile::wall());
+        map.explore(1, 1);
+        assert!(map[1][1].explored);
+        assert_eq!(notified(&mut map), vec![(1, 1, false, false)]);
+        map.explore(1, 1);
+        assert_eq!(notified(&mut map), vec![]);
+        // Nor is it lost when the tile is changed.
+        map.dig(1, 1);
+        assert!(map[1][1].explored);
+    }
+    
+    // A closed door is a way through, at the cost of opening it.
+    #[test]
+    fn paths_go_through_closed_doors() {
+        let mut map = Map::new(7, 3, Tile::wall());
+        for x in 1..6 {
+            map.set_tile(x, 1, Tile::empty());
+        }
+        map.set_tile(3, 1, Tile::door(false));
+        let path = find_path((1, 1), (5, 1), &map, CornerRule::NoSqueeze, &[], |_, _| true);
+        assert_eq!(path, Some(vec![(2, 1), (3, 1), (4, 1), (5, 1)]));
+    }
+    
+    // An open room of floor inside a ring of wall.
+    fn room(width: i32, height: i32) -> Map {
+        let mut map = Map::new(width, height, Tile::wall());
+        for x in 1..(width - 1) {
+            for y in 1..(height - 1) {
+                map.set_tile(x, y, Tile::empty());
+            }
+        }
+        map
+    }
+    
+    fn blocker(x: i32, y: i32) -> Object {
+        Object::new(x, y, 'o', "orc", (0.0, 0.0, 1.0), true, (false, 0, (0, 0, 0)))
+    }
+    
+    #[test]
+    fn paths_are_as_short_as_can_be() {
+        let map = room(10, 10);
+        assert_eq!(find_path((1, 1), (4, 4), &map, CornerRule::NoSqueeze, &[], |_, _| true), Some(vec![(2, 2), (3, 3), (4, 4)]));
+        assert_eq!(find_path((1, 1), (8, 3), &map, CornerRule::NoSqueeze, &[], |_, _| true).map(|path| path.len()), Some(7));
+        assert_eq!(find_path((2, 2), (2, 2), &map, CornerRule::NoSqueeze, &[], |_, _| true), Some(vec![]));
+    }
+    
+    #[test]
+    fn paths_go_round_walls() {
+        let mut map = room(9, 7);
+        for y in 1..5 {
+            map.set_tile(4, y, Tile::wall());
+        }
+        let path = find_path((2, 1), (6, 1), &map, CornerRule::NoSqueeze, &[], |_, _| true).expect("there is a way round");
+        assert!(path.contains(&(4, 5)), "{:?}", path);
+        assert_eq!(path.len(), 8);
+        
+        // Walled off completely, there is no way there.
+        map.set_tile(4, 5, Tile::wall());
+        assert_eq!(find_path((2, 1), (6, 1), &map, CornerRule::NoSqueeze, &[], |_, _| true), None);
+    }
+    
+    // Something in the way is walked round if there is room, and queued behind if not.
+    #[test]
+    fn paths_avoid_blocking_objects() {
+        let map = room(9, 5);
+        let path = find_path((1, 2), (7, 2), &map, CornerRule::NoSqueeze, &[blocker(4, 2)], |_, _| true).unwrap();
+        assert!(!path.contains(&(4, 2)) && path.len() == 6, "{:?}", path);
+        
+        let mut corridor = Map::new(9, 3, Tile::wall());
+        for x in 1..8 {
+            corridor.set_tile(x, 1, Tile::empty());
+        }
+        let path = find_path((1, 1), (7, 1), &corridor, CornerRule::NoSqueeze, &[blocker(4, 1)], |_, _| true).unwrap();
+        assert!(path.contains(&(4, 1)));
+    }
+    
+    #[test]
+    fn paths_keep_to_passable_tiles() {
+        let map = room(9, 5);
+        // The top row can't be walked along, but the goal can always be stepped onto.
+        let path = find_path((1, 1), (7, 1), &map, CornerRule::NoSqueeze, &[], |_, y| y != 1).unwrap();
+        assert!(path[..path.len() - 1].iter().all(|&(_, y)| y != 1), "{:?}", path);
+        assert_eq!(path.last(), Some(&(7, 1)));
+    }
+    
+    #[test]
+    fn the_nearest_goal_is_found() {
+        let map = room(12, 5);
+        let goal = |x: i32, _: i32| x == 1 || x == 9;
+        assert_eq!(find_path_to_nearest((7, 2), &map, CornerRule::NoSqueeze, &[], |_, _| true, goal).and_then(|path| path.last().cloned()).map(|(x, _)| x), Some(9));
+        // The start itself never counts as the goal.
+        let path = find_path_to_nearest((9, 2), &map, CornerRule::NoSqueeze, &[], |_, _| true, goal).unwrap();
+        assert_eq!(path.last().map(|&(x, _)| x), Some(9));
+        assert_eq!(path.len(), 1);
+        assert_eq!(find_path_to_nearest((5, 2), &map, CornerRule::NoSqueeze, &[], |_, _| true, |_, _| false), None);
+    }
+    
+    // With the NoSqueeze rule, a diagonal step can pass one wall corner but not between two.
+    #[test]
+    fn diagonal_steps_dont_squeeze_between_walls() {
+        let rule = CornerRule::NoSqueeze;
+        let mut map = room(6, 6);
+        map.set_tile(3, 2, Tile::wall());
+        assert!(can_pass_corner(2, 2, 1, 1, &map, rule));
+        assert!(can_pass_corner(2, 3, 1, -1, &map, rule));
+        map.set_tile(2, 3, Tile::wall());
+        assert!(!can_pass_corner(2, 2, 1, 1, &map, rule));
+        assert!(!can_pass_corner(3, 3, -1, -1, &map, rule));
+        assert!(can_pass_corner(2, 2, 1, 0, &map, rule), "straight steps are never stopped by corners");
+        
+        // Paths don't squeeze through either.
+        let path = find_path((2, 2), (3, 3), &map, rule, &[], |_, _| true);
+        assert!(path.as_ref().is_some_and(|path| path.len() > 1), "{:?}", path);
+    }
+    
+    // The other rules allow any diagonal step, or none past a wall corner.
+    #[test]
+    fn corner_rules_decide_which_diagonal_steps_are_allowed() {
+        let mut map = room(6, 6);
+        map.set_tile(3, 2, Tile::wall());
+        assert!(can_pass_corner(2, 2, 1, 1, &map, CornerRule::Allow));
+        assert!(!can_pass_corner(2, 2, 1, 1, &map, CornerRule::NoCutting));
+        assert!(can_pass_corner(2, 3, 1, 1, &map, CornerRule::NoCutting), "no corner to cut");
+        map.set_tile(2, 3, Tile::wall());
+        assert!(can_pass_corner(2, 2, 1, 1, &map, CornerRule::Allow));
+        assert_eq!(find_path((2, 2), (3, 3), &map, CornerRule::Allow, &[], |_, _| true), Some(vec![(3, 3)]));
+        
+        // Cutting no corners, the way round one wall is longer.
+        let mut map = room(6, 6);
+        map.set_tile(3, 2, Tile::wall());
+        assert_eq!(find_path((2, 2), (3, 3), &map, CornerRule::NoCutting, &[], |_, _| true).map(|path| path.len()), Some(2));
+    }
+}
@@ -0,0 +1,166 @@
+// The map in miniature: the overview screen, which shows the whole of what the player knows of the
+// map at once, and the minimap in the corner of the GUI panel. Both scale the map down to fit,
+// drawing each screen cell as two map cells, one above the other, with the half-block character.
+
+use tcod::console::*;
+use tcod::colors::*;
+
+use entities::*;
+use fov::*;
+use game::*;
+use lighting::*;
+use map::*;
+use rendering::*;
+
+// Code page 437's upper half block: the top half is drawn in the foreground colour, the bottom in
+// the background colour.
+pub const HALF_BLOCK: char = '\u{df}';
+
+pub const MINIMAP_WIDTH: i32 = 16;
+
+// Places out of view are drawn at these lightnesses; places in view are drawn as lit as they are,
+// so that lit areas stand out.
+const REMEMBERED_WALL_LIGHTNESS: f64 = 0.2;
+const REMEMBERED_FLOOR_LIGHTNESS: f64 = 0.3;
+const VISIBLE_WALL_LIGHTNESS: f64 = 0.35;
+
+const COLOR_MARK_PLAYER: (f64, f64, f64) = (300.0, 1.0, 0.7);
+const COLOR_MARK_ITEM: (f64, f64, f64) = (55.0, 1.0, 0.55);
+
+// What the player knows of a map tile, as the colour to draw it with. When a part of the overview
+// covers several tiles, the most important of them is drawn: the player, then items, then floors
+// (the best lit first), then walls.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Mark {
+    rank: u8,
+    colour: (f64, f64, f64),
+}
+
+const RANK_WALL: u8 = 1;
+const RANK_FLOOR: u8 = 2;
+const RANK_ITEM: u8 = 3;
+const RANK_PLAYER: u8 = 4;
+
+impl Mark {
+    fn outranks(&self, other: &Mark) -> bool {
+        (self.rank, self.colour.2) > (other.rank, other.colour.2)
+    }
+}
+
+// The marks for every tile of the map, as [x][y]; None for tiles the player knows nothing of.
+fn marks(game: &Game, objects: &[Object], fov_map: &FovMap, light_field: &LightField, tone_mapping: ToneMapping) -> Vec<Vec<Option<Mark>>> {
+    let (map_width, map_height) = map_size(&game.map);
+    let ambient_illumination = game.config.lighting.ambient_illumination;
+    let mut marks = vec![vec![None; map_height as usize]; map_width as usize];
+    for y in 0..map_height {
+        for x in 0..map_width {
+            let tile = &game.map[x as usize][y as usize];
+            if !tile.explored {
+                continue;
+            }
+            let visible = fov_map.is_in_fov(x, y);
+            let (rank, lightness) = match (tile.blocked && !tile.door, visible) {
+                (true, false) => (RANK_WALL, REMEMBERED_WALL_LIGHTNESS),
+                (true, true) => (RANK_WALL, VISIBLE_WALL_LIGHTNESS),
+                (false, false) => (RANK_FLOOR, REMEMBERED_FLOOR_LIGHTNESS),
+                (false, true) => {
+                    let lit = tone_map(light_field[x as usize][y as usize] + ambient_illumination, tone_mapping);
+                    (RANK_FLOOR, lit.max(REMEMBERED_FLOOR_LIGHTNESS))
+                }
+            };
+            let colour = tile.color_hsl;
+            marks[x as usize][y as usize] = Some(Mark { rank: rank, colour: (colour.0, colour.1, lightness) });
+        }
+    }
+    
+    // Items in view, and remembered where they were last seen out of view.
+    let items = objects.iter()
+        .filter(|object| object.item.is_some() && fov_map.is_in_fov(object.x, object.y))
+        .map(|object| object.pos())
+        .chain(game.remembered.iter()
+               .filter(|ghost| ghost.item && !fov_map.is_in_fov(ghost.x, ghost.y))
+               .map(|ghost| (ghost.x, ghost.y)));
+    for (x, y) in items {
+        marks[x as usize][y as usize] = Some(Mark { rank: RANK_ITEM, colour: COLOR_MARK_ITEM });
+    }
+    let (player_x, player_y) = objects[PLAYER].pos();
+    marks[player_x as usize][player_y as usize] = Some(Mark { rank: RANK_PLAYER, colour: COLOR_MARK_PLAYER });
+    marks
+}
+
+// Draw the map, scaled down to fit the given area of a console (and centred in it), never scaled
+// up. Each screen cell shows two parts of the map, one above the other.
+fn draw_marks(con: &mut Offscreen, area: (i32, i32, i32, i32), marks: &[Vec<Option<Mark>>]) {
+    let (area_x, area_y, width, height) = area;
+    let map_width = marks.len() as i32;
+    let map_height = marks.first().map_or(0, |column| column.len() as i32);
+    if width <= 0 || height <= 0 || map_width == 0 || map_height == 0 {
+        return;
+    }
+    
+    // Map tiles per screen cell across, and per half cell down.
+    let scale = (map_width as f64 / width as f64).max(map_height as f64 / (2 * height) as f64).max(1.0);
+    let (cells_x, half_cells_y) = ((map_width as f64 / scale).ceil() as i32, (map_height as f64 / scale).ceil() as i32);
+    let (offset_x, offset_y) = (area_x + (width - cells_x) / 2, area_y + (height - (half_cells_y + 1) / 2) / 2);
+    
+    // The most important mark among the tiles a part of the screen covers.
+    let part = |column: i32, row: i32| {
+        let (start_x, start_y) = ((column as f64 * scale) as i32, (row as f64 * scale) as i32);
+        let end_x = ((((column + 1) as f64 * scale) as i32).max(start_x + 1)).min(map_width);
+        let end_y = ((((row + 1) as f64 * scale) as i32).max(start_y + 1)).min(map_height);
+        let mut best: Option<Mark> = None;
+        for x in start_x..end_x {
+            for y in start_y..end_y {
+                if let Some(mark) = marks[x as usize][y as usize] {
+                    if best.is_none_or(|best| mark.outranks(&best)) {
+                        best = Some(mark);
+                    }
+                }
+            }
+        }
+        best.map_or(BLACK, |mark| return_rgb_colour(mark.colour))
+    };
+    
+    for row in 0..(half_cells_y + 1) / 2 {
+        for column in 0..cells_x {
+            let top = part(column, row * 2);
+            let bottom = if row * 2 + 1 < half_cells_y { part(column, row * 2 + 1) } else { BLACK };
+            con.put_char_ex(offset_x + column, offset_y + row, HALF_BLOCK, top, bottom);
+        }
+    }
+}
+
+// Draw the minimap into the given area of the GUI panel.
+pub fn render_minimap(panel: &mut Offscreen, area: (i32, i32, i32, i32), game: &Game, objects: &[Object], fov_map: &FovMap,
+                      light_field: &LightField, tone_mapping: ToneMapping) {
+    draw_marks(panel, area, &marks(game, objects, fov_map, light_field, tone_mapping));
+}
+
+// Show the overview, full screen, until any key is pressed.
+pub fn overview(tcod: &mut Tcod, game: &Game, objects: &[Object], fov_map: &FovMap, light_field: &LightField) {
+    let (screen_width, screen_height) = (tcod.root.width(), tcod.root.height());
+    let mut window = Offscreen::new(screen_width, screen_height);
+    window.set_default_background(BLACK);
+    window.clear();
+    window.set_default_foreground(WHITE);
+    window.print_ex(screen_width / 2, 0, BackgroundFlag::None, TextAlignment::Center,
+                    format!("Depth {} (any key to close)", game.depth));
+    
+    let marks = marks(game, objects, fov_map, light_field, tcod.settings.tone_mapping);
+    draw_marks(&mut window, (0, 1, screen_width, screen_height - 2), &marks);
+    
+    // A key to the marks, along the bottom.
+    let legend = [("You", COLOR_MARK_PLAYER), ("Items", COLOR_MARK_ITEM)];
+    let mut x = 1;
+    for &(name, colour) in &legend {
+        window.put_char_ex(x, screen_height - 1, HALF_BLOCK, return_rgb_colour(colour), return_rgb_colour(colour));
+        window.set_default_foreground(WHITE);
+        window.print_ex(x + 2, screen_height - 1, BackgroundFlag::None, TextAlignment::Left, name);
+        x += name.len() as i32 + 4;
+    }
+    window.print_ex(x, screen_height - 1, BackgroundFlag::None, TextAlignment::Left, "Brighter floors are lit and in view.");
+    
+    blit(&window, (0, 0), (screen_width, screen_height), &mut tcod.root, (0, 0), 1.0, 1.0);
+    tcod.flush();
+    tcod.wait_for_keypress();
+}
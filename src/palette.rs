@@ -0,0 +1,229 @@
+// Palettes: ways of recolouring the finished frame for players who can't tell the game's colours
+// apart. The game's colours are all HSL, and lighting only changes their lightness, so hue is left
+// to tell walls from floors and friends from foes; under colour blindness it often can't. Palettes
+// are applied last of all, after lighting, to everything drawn, so that nothing is missed.
+
+use tcod::colors::Color;
+use tcod::console::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Palette {
+    Normal,
+    Deuteranopia,   // Green-blind: the lost red-green difference is shifted into green and blue.
+    Protanopia,     // Red-blind: likewise.
+    Tritanopia,     // Blue-blind: the lost blue-yellow difference is shifted into red and green.
+    HighContrast,   // More saturated, with lightness pushed away from the middle (but dark
+                    // colours keep at least half theirs, so that unlit places stay visible).
+    Monochrome,     // Lightness alone, in greys.
+}
+
+// How each kind of colour blindness sees an RGB colour, as in Machado, Oliveira and Fernandes
+// (2009), at full severity. What the player would fail to see is then shifted into channels they
+// can see ("daltonisation").
+const PROTANOPIA: [[f64; 3]; 3] = [
+    [0.152286, 1.052583, -0.204868],
+    [0.114503, 0.786281, 0.099216],
+    [-0.003882, -0.048116, 1.051998],
+];
+const DEUTERANOPIA: [[f64; 3]; 3] = [
+    [0.367322, 0.860646, -0.227968],
+    [0.280085, 0.672501, 0.047413],
+    [-0.011820, 0.042940, 0.968881],
+];
+const TRITANOPIA: [[f64; 3]; 3] = [
+    [1.255528, -0.076749, -0.178779],
+    [-0.078411, 0.930809, 0.147602],
+    [0.004733, 0.691367, 0.303900],
+];
+// How the red-green difference is shifted into green and blue, for protanopia and deuteranopia.
+const RED_GREEN_SHIFT: [[f64; 3]; 3] = [
+    [0.0, 0.0, 0.0],
+    [0.7, 1.0, 0.0],
+    [0.7, 0.0, 1.0],
+];
+// How the blue-yellow difference is shifted into red and green, for tritanopia.
+const BLUE_YELLOW_SHIFT: [[f64; 3]; 3] = [
+    [0.0, 0.0, 0.7],
+    [0.0, 0.0, 0.7],
+    [0.0, 0.0, 0.0],
+];
+
+const HIGH_CONTRAST_SATURATION: f64 = 1.5;
+const HIGH_CONTRAST_LIGHTNESS: f64 = 1.6;
+
+impl Palette {
+    pub fn next(self) -> Palette {
+        match self {
+            Palette::Normal => Palette::Deuteranopia,
+            Palette::Deuteranopia => Palette::Protanopia,
+            Palette::Protanopia => Palette::Tritanopia,
+            Palette::Tritanopia => Palette::HighContrast,
+            Palette::HighContrast => Palette::Monochrome,
+            Palette::Monochrome => Palette::Normal,
+        }
+    }
+    
+    // Recolour a single colour.
+    pub fn apply(self, colour: Color) -> Color {
+        let rgb = [colour.r as f64, colour.g as f64, colour.b as f64];
+        let recoloured = match self {
+            Palette::Normal => return colour,
+            Palette::Deuteranopia => daltonise(rgb, &DEUTERANOPIA, &RED_GREEN_SHIFT),
+            Palette::Protanopia => daltonise(rgb, &PROTANOPIA, &RED_GREEN_SHIFT),
+            Palette::Tritanopia => daltonise(rgb, &TRITANOPIA, &BLUE_YELLOW_SHIFT),
+            Palette::HighContrast => high_contrast(colour),
+            Palette::Monochrome => {
+                let grey = 0.299 * rgb[0] + 0.587 * rgb[1] + 0.114 * rgb[2];
+                [grey, grey, grey]
+            }
+        };
+        Color { r: to_channel(recoloured[0]), g: to_channel(recoloured[1]), b: to_channel(recoloured[2]) }
+    }
+    
+    // Recolour every cell of a console, foreground and background.
+    pub fn apply_to(self, console: &mut Offscreen) {
+        if self == Palette::Normal {
+            return;
+        }
+        for y in 0..console.height() {
+            for x in 0..console.width() {
+                let foreground = self.apply(console.get_char_foreground(x, y));
+                let background = self.apply(console.get_char_background(x, y));
+                console.set_char_foreground(x, y, foreground);
+                console.set_char_background(x, y, background, BackgroundFlag::Set);
+            }
+        }
+    }
+}
+
+fn transform(matrix: &[[f64; 3]; 3], v: [f64; 3]) -> [f64; 3] {
+    let mut result = [0.0; 3];
+    for (i, row) in matrix.iter().enumerate() {
+        result[i] = row[0] * v[0] + row[1] * v[1] + row[2] * v[2];
+    }
+    result
+}
+
+// Simulate how a colour-blind eye sees the colour, and add what it misses back in where it can see.
+fn daltonise(rgb: [f64; 3], simulation: &[[f64; 3]; 3], shift: &[[f64; 3]; 3]) -> [f64; 3] {
+    let seen = transform(simulation, rgb);
+    let missed = [rgb[0] - seen[0], rgb[1] - seen[1], rgb[2] - seen[2]];
+    let correction = transform(shift, missed);
+    [rgb[0] + correction[0], rgb[1] + correction[1], rgb[2] + correction[2]]
+}
+
+fn high_contrast(colour: Color) -> [f64; 3] {
+    use hsl::*;
+    
+    let hsl = HSL::from_rgb(&[colour.r, colour.g, colour.b]);
+    let stretched = HSL {
+        h: hsl.h,
+        s: (hsl.s * HIGH_CONTRAST_SATURATION).min(1.0),
+        l: (0.5 + (hsl.l - 0.5) * HIGH_CONTRAST_LIGHTNESS).max(hsl.l * 0.5).min(1.0),
+    };
+    let (r, g, b) = stretched.to_rgb();
+    [r as f64, g as f64, b as f64]
+}
+
+fn to_channel(value: f64) -> u8 {
+    (value.round() as i32).clamp(0, 255) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hsl::HSL;
+    
+    const RED: Color = Color { r: 200, g: 40, b: 40 };
+    const GREEN: Color = Color { r: 40, g: 160, b: 40 };
+    const BLUE: Color = Color { r: 40, g: 40, b: 200 };
+    const YELLOW: Color = Color { r: 200, g: 200, b: 40 };
+    
+    fn rgb(colour: Color) -> [f64; 3] {
+        [colour.r as f64, colour.g as f64, colour.b as f64]
+    }
+    
+    // How far apart two colours look to an eye that sees through 'simulation'.
+    fn seen_distance(simulation: &[[f64; 3]; 3], a: Color, b: Color) -> f64 {
+        let (a, b) = (transform(simulation, rgb(a)), transform(simulation, rgb(b)));
+        ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+    }
+    
+    fn lightness(colour: Color) -> f64 {
+        HSL::from_rgb(&[colour.r, colour.g, colour.b]).l
+    }
+    
+    #[test]
+    fn palettes_cycle_through_them_all() {
+        let mut palette = Palette::Normal;
+        let mut seen = vec![];
+        loop {
+            seen.push(palette);
+            palette = palette.next();
+            if palette == Palette::Normal {
+                break;
+            }
+        }
+        assert_eq!(seen.len(), 6);
+    }
+    
+    #[test]
+    fn the_normal_palette_changes_nothing() {
+        for &colour in &[RED, GREEN, BLUE, YELLOW] {
+            assert_eq!(Palette::Normal.apply(colour), colour);
+        }
+    }
+    
+    // Everyone sees greys alike, so daltonisation leaves them be.
+    #[test]
+    fn daltonisation_leaves_greys_alone() {
+        for &palette in &[Palette::Deuteranopia, Palette::Protanopia, Palette::Tritanopia] {
+            for &level in &[0, 64, 128, 255] {
+                let grey = Color { r: level, g: level, b: level };
+                let recoloured = palette.apply(grey);
+                for (&channel, &original) in rgb(recoloured).iter().zip(&rgb(grey)) {
+                    assert!((channel - original).abs() <= 1.0, "{:?} turned {:?} into {:?}", palette, grey, recoloured);
+                }
+            }
+        }
+    }
+    
+    // Colours that a colour-blind player confuses look further apart to them once recoloured.
+    #[test]
+    fn daltonisation_separates_confused_colours() {
+        let cases = [
+            (Palette::Deuteranopia, &DEUTERANOPIA, RED, GREEN),
+            (Palette::Protanopia, &PROTANOPIA, RED, GREEN),
+            (Palette::Tritanopia, &TRITANOPIA, BLUE, GREEN),
+            (Palette::Tritanopia, &TRITANOPIA, YELLOW, Color { r: 200, g: 200, b: 200 }),
+        ];
+        for &(palette, simulation, a, b) in &cases {
+            let before = seen_distance(simulation, a, b);
+            let after = seen_distance(simulation, palette.apply(a), palette.apply(b));
+            assert!(after > before, "{:?}: {:?} and {:?} were {} apart, now {}", palette, a, b, before, after);
+        }
+    }
+    
+    #[test]
+    fn monochrome_keeps_only_lightness() {
+        for &colour in &[RED, GREEN, BLUE, YELLOW] {
+            let grey = Palette::Monochrome.apply(colour);
+            assert!(grey.r == grey.g && grey.g == grey.b, "{:?} became {:?}", colour, grey);
+        }
+        assert_eq!(Palette::Monochrome.apply(Color { r: 255, g: 255, b: 255 }), Color { r: 255, g: 255, b: 255 });
+        assert_eq!(Palette::Monochrome.apply(Color { r: 0, g: 0, b: 0 }), Color { r: 0, g: 0, b: 0 });
+        assert!(Palette::Monochrome.apply(YELLOW).r > Palette::Monochrome.apply(BLUE).r);
+    }
+    
+    #[test]
+    fn high_contrast_pushes_lightness_apart() {
+        let light = Color { r: 180, g: 180, b: 150 };
+        let dark = Color { r: 60, g: 50, b: 50 };
+        assert!(lightness(Palette::HighContrast.apply(light)) > lightness(light));
+        assert!(lightness(Palette::HighContrast.apply(dark)) < lightness(dark));
+        
+        // But dark colours keep at least half their lightness.
+        let very_dark = Color { r: 20, g: 15, b: 15 };
+        assert!(lightness(Palette::HighContrast.apply(very_dark)) >= lightness(very_dark) * 0.5 - 0.01);
+    }
+}
@@ -0,0 +1,730 @@
+// Drawing: the window, the map, the GUI panel, menus and the other screens.
+
+use std::cmp;
+
+use tcod::console::*;
+use tcod::colors::*;
+
+use config::*;
+use effects::*;
+use entities::*;
+use fov::*;
+use game::*;
+use input::*;
+use items::*;
+use lighting::*;
+use map::*;
+use minimap::*;
+use palette::*;
+use terminal::*;
+use tileset::*;
+
+pub const LIMIT_FPS: i32 = 20;     // The default; it can be changed on the options screen.
+pub const FONT_PATH: &'static str = "arial10x10.png";
+const TCOD_LAYOUT_HALF_BLOCK: (i32, i32) = (27, 1);    // Column and row of the glyph in the font.
+pub const FPS_CHOICES: &'static [i32] = &[10, 20, 30, 60];
+
+// The GUI panel below the map: bars and status on the left, the latest messages on the right. It
+// takes up whatever room the map leaves at the bottom of the screen.
+const BAR_WIDTH: i32 = 20;
+const MSG_X: i32 = BAR_WIDTH + 2;
+
+pub const COLOR_TARGET: (f64, f64, f64) = (0.0, 0.0, 0.75);
+
+// Remembered objects are drawn at this lightness, whatever light they were seen in.
+const GHOST_LIGHTNESS: f64 = 0.3;
+
+const COLOR_HP_BAR: (f64, f64, f64) = (0.0, 0.8, 0.5);
+const COLOR_HP_BAR_BACK: (f64, f64, f64) = (0.0, 0.8, 0.2);
+const COLOR_MANA_BAR: (f64, f64, f64) = (220.0, 0.8, 0.5);
+const COLOR_MANA_BAR_BACK: (f64, f64, f64) = (220.0, 0.8, 0.2);
+const COLOR_LIGHT_BAR: (f64, f64, f64) = (45.0, 1.0, 0.6);
+const COLOR_LIGHT_BAR_BACK: (f64, f64, f64) = (45.0, 1.0, 0.2);
+const COLOR_PANEL_TEXT: (f64, f64, f64) = (0.0, 0.0, 0.9);
+
+pub const HELP_SCREEN_WIDTH: i32 = 50;
+const TOOLTIP_MAX_WIDTH: i32 = 30;
+pub const INVENTORY_WIDTH: i32 = 50;
+pub const CHARACTER_SCREEN_WIDTH: i32 = 40;
+pub const MAIN_MENU_WIDTH: i32 = 24;
+pub const OPTIONS_WIDTH: i32 = 40;
+pub const KEY_BINDINGS_WIDTH: i32 = 60;
+pub const ENDING_WIDTH: i32 = 40;
+
+// The libtcod consoles, bundled together so they can be passed around as one, along with the
+// display settings and the tilesets they can be drawn with, and the window frames are shown in.
+// Frames are composed in 'root', whichever front-end shows them. The field of view map is kept
+// separately, as game logic needs it even when there is no window (replays).
+pub struct Tcod {
+    pub root: Offscreen,
+    pub con: Offscreen,
+    pub panel: Offscreen,
+    pub settings: Settings,
+    pub tilesets: Vec<Tileset>,
+    pub window: Window,
+}
+
+// Where frames are shown, and input comes from: a libtcod window, or the terminal.
+pub enum Window {
+    Sdl(Root),
+    Terminal(Terminal),
+}
+
+impl Tcod {
+    // Open the window, with the default display settings and the configured tileset (or ASCII, if
+    // its sheet can't be found), along with the off-screen consoles in which each frame's map and
+    // GUI panel are composed.
+    pub fn new(config: &Config, tilesets: Vec<Tileset>) -> Tcod {
+        let tileset = tilesets.iter().find(|tileset| tileset.name == config.screen.tileset)
+            .cloned()
+            .unwrap_or_else(Tileset::ascii);
+        let tileset = if tileset.is_available() {
+            tileset
+        } else {
+            eprintln!("The sheet for tileset '{}' could not be found, so the {} tileset is used instead.", tileset.name, ASCII_TILESET);
+            Tileset::ascii()
+        };
+        let settings = Settings { font: FONT_PATH.into(), fps: LIMIT_FPS, tone_mapping: ToneMapping::Exponential, palette: Palette::Normal, effects: true, minimap: true, tileset: tileset };
+        let window = init_root(&settings, config.screen.width, config.screen.height);
+        tcod::system::set_fps(settings.fps);
+        Tcod::with_window(config, settings, tilesets, Window::Sdl(window))
+    }
+    
+    // Take over the terminal instead of opening a window. Terminals have no sprites, so only the
+    // ASCII tileset is offered.
+    pub fn new_terminal(config: &Config, colours: TerminalColours) -> Result<Tcod, String> {
+        let terminal = Terminal::new(config.screen.width, config.screen.height, colours)?;
+        let settings = Settings { font: FONT_PATH.into(), fps: LIMIT_FPS, tone_mapping: ToneMapping::Exponential, palette: Palette::Normal, effects: true, minimap: true, tileset: Tileset::ascii() };
+        Ok(Tcod::with_window(config, settings, vec![Tileset::ascii()], Window::Terminal(terminal)))
+    }
+    
+    fn with_window(config: &Config, settings: Settings, tilesets: Vec<Tileset>, window: Window) -> Tcod {
+        Tcod {
+            root: Offscreen::new(config.screen.width, config.screen.height),
+            con: Offscreen::new(config.map.width, config.map.height),
+            panel: Offscreen::new(config.screen.width, config.panel_height()),
+            settings: settings,
+            tilesets: tilesets,
+            window: window,
+        }
+    }
+    
+    // The frame composed in the root console, as it is shown: in the chosen palette. The palette is
+    // applied to a copy, as the root console is often shown again without being drawn afresh (by
+    // menus).
+    pub fn frame(&self) -> Offscreen {
+        let (width, height) = (self.root.width(), self.root.height());
+        let mut frame = Offscreen::new(width, height);
+        blit(&self.root, (0, 0), (width, height), &mut frame, (0, 0), 1.0, 1.0);
+        self.settings.palette.apply_to(&mut frame);
+        frame
+    }
+    
+    // Show the frame composed in the root console.
+    pub fn flush(&mut self) {
+        let (width, height) = (self.root.width(), self.root.height());
+        let recoloured;
+        let frame = if self.settings.palette == Palette::Normal {
+            &self.root
+        } else {
+            recoloured = self.frame();
+            &recoloured
+        };
+        match self.window {
+            Window::Sdl(ref mut window) => {
+                blit(frame, (0, 0), (width, height), window, (0, 0), 1.0, 1.0);
+                window.flush();
+            }
+            Window::Terminal(ref mut terminal) => {
+                if let Err(e) = terminal.draw(frame, self.settings.fps) {
+                    eprintln!("Could not draw to the terminal: {}", e);
+                }
+            }
+        }
+    }
+    
+    pub fn wait_for_keypress(&mut self) -> tcod::input::Key {
+        match self.window {
+            Window::Sdl(ref mut window) => window.wait_for_keypress(true),
+            Window::Terminal(ref mut terminal) => terminal.wait_for_keypress(),
+        }
+    }
+    
+    // The next input event, if there is one waiting. The terminal only has key presses and mouse
+    // events, whatever is asked for.
+    pub fn check_for_event(&mut self, flags: tcod::input::EventFlags) -> Option<tcod::input::Event> {
+        match self.window {
+            Window::Sdl(_) => tcod::input::check_for_event(flags).map(|e| e.1),
+            Window::Terminal(ref mut terminal) => terminal.check_for_event(),
+        }
+    }
+    
+    pub fn window_closed(&self) -> bool {
+        match self.window {
+            Window::Sdl(ref window) => window.window_closed(),
+            Window::Terminal(ref terminal) => terminal.is_closed(),
+        }
+    }
+    
+    pub fn toggle_fullscreen(&mut self) {
+        if let Window::Sdl(ref mut window) = self.window {
+            let fullscreen = window.is_fullscreen();
+            window.set_fullscreen(!fullscreen);
+        }
+    }
+    
+    // Open the window again with the current settings, for a change of font or tileset to take
+    // effect. A terminal has no font to change.
+    pub fn reopen(&mut self) {
+        let (width, height) = (self.root.width(), self.root.height());
+        if let Window::Sdl(ref mut window) = self.window {
+            *window = init_root(&self.settings, width, height);
+        }
+    }
+    
+    // Where the message log is drawn on the root console, as (x, y, width, height): the right of
+    // the GUI panel, which sits below the map, up to the minimap, if it is shown.
+    pub fn message_area(&self) -> (i32, i32, i32, i32) {
+        let minimap_width = if self.settings.minimap { MINIMAP_WIDTH + 1 } else { 0 };
+        (MSG_X, self.con.height(), self.panel.width() - MSG_X - minimap_width, self.panel.height())
+    }
+}
+
+// Display settings, which can be changed on the options screen while the game is running.
+pub struct Settings {
+    pub font: String,           // Only used by the ASCII tileset; others bring their own sheet.
+    pub fps: i32,
+    pub tone_mapping: ToneMapping,
+    pub palette: Palette,
+    pub effects: bool,          // Whether particles, projectiles and flashes are shown.
+    pub minimap: bool,          // Whether the minimap is shown in the corner of the GUI panel.
+    pub tileset: Tileset,
+}
+
+// Create the root console with the given settings' font (or tileset sheet) and size, in
+// characters. Calling this again replaces the window, which is how the font and tileset are
+// changed.
+pub fn init_root(settings: &Settings, width: i32, height: i32) -> Root {
+    let tileset = &settings.tileset;
+    let (font, layout, dimensions) = if tileset.sheet.is_empty() {
+        // Fonts follow libtcod's naming: "_ro" fonts are laid out in rows, "_tc" fonts (and the
+        // default) in libtcod's own layout, and anything else in columns. Their size is left to
+        // libtcod to work out from the layout.
+        let font = settings.font.as_str();
+        let layout = if font.contains("_ro") {
+            FontLayout::AsciiInRow
+        } else if font.contains("_tc") || font == FONT_PATH {
+            FontLayout::Tcod
+        } else {
+            FontLayout::AsciiInCol
+        };
+        (font, layout, (0, 0))
+    } else {
+        // Tileset sheets are laid out in rows, of the size the tileset gives.
+        (tileset.sheet.as_str(), FontLayout::AsciiInRow, (tileset.columns as i32, tileset.rows as i32))
+    };
+    let tcod_layout = matches!(layout, FontLayout::Tcod);
+    let mut root = Root::initializer()
+        // Configuration.
+        .font(font, layout)
+        .font_type(FontType::Greyscale)
+        .font_dimensions(dimensions.0, dimensions.1)
+        .size(width, height)
+        .title("Rust/libcod tutorial")
+        .init();
+    if tcod_layout {
+        // libtcod's own layout has no place for code page 437's upper half block, which the
+        // minimap is drawn with, but it has the same glyph as one of its subpixel characters.
+        root.map_ascii_code_to_font(HALF_BLOCK as i32, TCOD_LAYOUT_HALF_BLOCK.0, TCOD_LAYOUT_HALF_BLOCK.1);
+    }
+    root
+}
+
+// The fonts that can be chosen on the options screen: every PNG file in the current directory.
+pub fn available_fonts() -> Vec<String> {
+    let mut fonts: Vec<String> = std::fs::read_dir(".")
+        .map(|entries| {
+            entries.filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| name.ends_with(".png"))
+                .collect()
+        })
+        .unwrap_or_default();
+    fonts.sort();
+    fonts
+}
+
+// Display a lettered menu of options over the map, centred on the screen, and wait for a choice.
+pub fn menu<T: AsRef<str>>(header: &str, options: &[T], width: i32, tcod: &mut Tcod) -> Option<usize> {
+    render_menu(header, options, width, &mut tcod.root);
+    
+    // Present the root console to the player and wait for a key-press.
+    tcod.flush();
+    let key = tcod.wait_for_keypress();
+    menu_choice(&key, options.len())
+}
+
+// Draw a lettered menu of options over the map, centred on the screen.
+pub fn render_menu<T: AsRef<str>>(header: &str, options: &[T], width: i32, root: &mut Offscreen) {
+    assert!(options.len() <= 26, "Cannot have a menu with more than 26 options.");
+    
+    // Calculate total height for the header (after auto-wrap) and one line per option.
+    let header_height = if header.is_empty() {
+        0
+    } else {
+        root.get_height_rect(0, 0, width, root.height(), header)
+    };
+    let height = options.len() as i32 + header_height;
+    
+    // Create an off-screen console that represents the menu's window.
+    let mut window = Offscreen::new(width, height);
+    
+    // Print the header, with auto-wrap.
+    window.set_default_foreground(WHITE);
+    window.print_rect_ex(0, 0, width, height, BackgroundFlag::None, TextAlignment::Left, header);
+    
+    // Print all the options.
+    for (index, option_text) in options.iter().enumerate() {
+        let menu_letter = (b'a' + index as u8) as char;
+        let text = format!("({}) {}", menu_letter, option_text.as_ref());
+        window.print_ex(0, header_height + index as i32, BackgroundFlag::None, TextAlignment::Left, text);
+    }
+    
+    // Blit the contents of "window" to the root console, over the map.
+    let x = root.width() / 2 - width / 2;
+    let y = root.height() / 2 - height / 2;
+    blit(&window, (0, 0), (width, height), root, (x, y), 1.0, 0.7);
+}
+
+// The menu option a key chooses, if any, out of 'count' options.
+pub fn menu_choice(key: &tcod::input::Key, count: usize) -> Option<usize> {
+    // Convert the ASCII code to an index; if it corresponds to an option, return it.
+    if key.printable.is_alphabetic() {
+        let index = key.printable.to_ascii_lowercase() as usize - 'a' as usize;
+        if index < count {
+            Some(index)
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+// A menu with no options, to display some text.
+pub fn msgbox(text: &str, width: i32, tcod: &mut Tcod) {
+    let options: &[&str] = &[];
+    menu(text, options, width, tcod);
+}
+
+// The inventory menu's options: each item of the inventory, or a note that there are none.
+pub fn inventory_options(inventory: &[Object]) -> Vec<String> {
+    if inventory.is_empty() {
+        vec!["Inventory is empty.".into()]
+    } else {
+        inventory.iter().map(|item| item.describe()).collect()
+    }
+}
+
+pub fn render_all(tcod: &mut Tcod, game: &mut Game, objects: &[Object], fov_map: &FovMap, fov_recompute: bool, light_field: &mut LightField, light_cache: &mut LightCache, effects: &Effects) {
+    let tone_mapping = tcod.settings.tone_mapping;
+    let ambient_illumination = game.config.lighting.ambient_illumination;
+    let modulation = game.config.lighting.illumination_modulation;
+    let root = &mut tcod.root;
+    let con = &mut tcod.con;
+    let tileset = &tcod.settings.tileset;
+    let map = &mut game.map;
+    let (map_width, map_height) = map_size(map);
+    
+    if fov_recompute {
+        // The FOV itself has already been recomputed by 'recompute_fov', as the game logic
+        // depends on it; only the lighting is done here.
+        
+        // Update illumination map.
+        for y in 0..map_height {
+            for x in 0..map_width {
+                light_field[x as usize][y as usize] = 0;
+            }
+        }
+        for object in objects.iter().chain(game.flashes.iter()) {
+            if object.light_source.0 {
+                // Here calculate the light field generated by the object light source.
+                //
+                // ...then, composit this into the illumination map, light_field.
+                //
+                // When we draw tiles below, we will get ther luminance value from the
+                // illumination map.
+                let &(ref object_light_field, map_start_offset, map_end_offset) = light_cache.light_field(map, object, modulation);
+                
+                'y_loop: for y in (map_start_offset.1)..(map_end_offset.1) {
+                            if (y < 0) || (y > (map_height - 1)) {
+                                continue 'y_loop;
+                            }
+                            'x_loop: for x in (map_start_offset.0)..(map_end_offset.0) {
+                                        if (x < 0) || (x > (map_width - 1)) {
+                                            continue 'x_loop;
+                                        }
+                                        
+                                        light_field[x as usize][y as usize] += object_light_field[(x - map_start_offset.0) as usize][(y - map_start_offset.1) as usize];
+                                     }
+                            }
+            }
+        }
+        
+        // Bursts of light from the effects layer, fading as they go out.
+        for (light, strength) in effects.lights() {
+            let &(ref burst_light_field, start, end) = light_cache.light_field(map, &light, modulation);
+            for y in cmp::max(0, start.1)..cmp::min(map_height, end.1) {
+                for x in cmp::max(0, start.0)..cmp::min(map_width, end.0) {
+                    let burst_light = burst_light_field[(x - start.0) as usize][(y - start.1) as usize];
+                    light_field[x as usize][y as usize] += (burst_light as f64 * strength) as i32;
+                }
+            }
+        }
+        light_cache.forget_unused();
+        
+        // Draw all world tiles.
+        for y in 0..map_height {
+            for x in 0..map_width {
+                let visible = fov_map.is_in_fov(x, y);
+                
+                let mut wall_color = map[x as usize][y as usize].color_hsl;
+                
+                if visible {
+                    //wall_color.2 = wall_color.2 + IN_FOV_LIGHTNESS_MODIFIER;
+                    wall_color.2 = tone_map(light_field[x as usize][y as usize] + ambient_illumination, tone_mapping);
+                    map.explore(x, y);
+                } else {
+                    wall_color.2 = tone_map(ambient_illumination, tone_mapping);
+                }
+                
+                if map[x as usize][y as usize].explored {
+                    // Tiles the tileset has a sprite for are drawn as the sprite, lit as objects
+                    // are; the rest as a lit background.
+                    match tileset.tile_glyph(&map[x as usize][y as usize]) {
+                        Some(glyph) => con.put_char_ex(x, y, glyph, return_rgb_colour(wall_color), BLACK),
+                        None => con.set_char_background(x, y, return_rgb_colour(wall_color), BackgroundFlag::Set),
+                    }
+                }
+            }
+        }
+    }
+    
+    // Draw what the player remembers seeing in the places they can't see now.
+    for ghost in game.remembered.iter().filter(|ghost| !fov_map.is_in_fov(ghost.x, ghost.y)) {
+        ghost.draw(con, tileset.object_glyph(&ghost.name, ghost.char), return_rgb_colour, GHOST_LIGHTNESS);
+    }
+    
+    // Draw all world objects.
+    //
+    // Only objects in the FOV are drawn, and non-blocking objects (corpses etc) are drawn first so
+    // that anything standing on top of them is drawn over them.
+    //
+    let mut to_draw: Vec<_> = objects.iter().filter(|o| fov_map.is_in_fov(o.x, o.y)).collect();
+    to_draw.sort_by_key(|object| object.blocks);
+    for object in &to_draw {
+        let log_lum_val = tone_map(light_field[object.x as usize][object.y as usize] + ambient_illumination, tone_mapping);
+        object.draw(con, tileset.object_glyph(&object.name, object.char), return_rgb_colour, log_lum_val);
+    }
+    
+    // Blit the composition terminal contents into the root terminal.
+    blit(con, (0,0), (map_width, map_height), root, (0,0), 1.0, 1.0);
+}
+
+// Draw the GUI panel below the map: the player's health and mana, the light falling on them and
+// the fuel left in the light they hold, the dungeon level and turn, the latest messages and the
+// minimap.
+pub fn render_panel(tcod: &mut Tcod, game: &Game, objects: &[Object], fov_map: &FovMap, light_field: &LightField) {
+    let tone_mapping = tcod.settings.tone_mapping;
+    let show_minimap = tcod.settings.minimap;
+    let ambient_illumination = game.config.lighting.ambient_illumination;
+    let (_, panel_y, msg_width, msg_height) = tcod.message_area();
+    let panel = &mut tcod.panel;
+    panel.set_default_background(BLACK);
+    panel.clear();
+    
+    let player = &objects[PLAYER];
+    let hp = player.fighter.map_or(0, |f| f.hp);
+    render_bar(panel, 1, 0, BAR_WIDTH, "HP", hp, player.max_hp(PLAYER, game), COLOR_HP_BAR, COLOR_HP_BAR_BACK);
+    let (mana, max_mana) = player.fighter.map_or((0, 0), |f| (f.mana, f.max_mana));
+    render_bar(panel, 1, 1, BAR_WIDTH, "Mana", mana, max_mana, COLOR_MANA_BAR, COLOR_MANA_BAR_BACK);
+    
+    // Light exposure, as the lightness the player is drawn with, in percent.
+    let light = tone_map(light_field[player.x as usize][player.y as usize] + ambient_illumination, tone_mapping);
+    render_bar(panel, 1, 2, BAR_WIDTH, "Light", (light * 100.0).round() as i32, 100, COLOR_LIGHT_BAR, COLOR_LIGHT_BAR_BACK);
+    
+    panel.set_default_foreground(return_rgb_colour(COLOR_PANEL_TEXT));
+    let held_light = get_equipped_in_slot(Slot::OffHand, &game.inventory)
+        .map(|id| &game.inventory[id])
+        .filter(|item| item.light_intensity > 0);
+    let fuel = match held_light {
+        Some(item) => match item.light_fuel {
+            Some(fuel) => format!("{}: {} turns", item.name, fuel),
+            None => item.name.clone(),
+        },
+        None => "No light held".into(),
+    };
+    panel.print_ex(1, 3, BackgroundFlag::None, TextAlignment::Left, fuel);
+    panel.print_ex(1, 4, BackgroundFlag::None, TextAlignment::Left, format!("Depth {}  Turn {}", game.depth, game.clock.turns()));
+    
+    // The latest messages, newest at the bottom, word-wrapped.
+    let mut y = msg_height;
+    for &(ref message, colour) in game.messages.messages.iter().rev() {
+        let message_height = panel.get_height_rect(MSG_X, y, msg_width, 0, message);
+        y -= message_height;
+        if y < 0 {
+            break;
+        }
+        panel.set_default_foreground(return_rgb_colour(colour));
+        panel.print_rect(MSG_X, y, msg_width, 0, message);
+    }
+    
+    let (panel_width, panel_height) = (panel.width(), panel.height());
+    if show_minimap {
+        let area = (panel_width - MINIMAP_WIDTH, 0, MINIMAP_WIDTH, panel_height);
+        render_minimap(panel, area, game, objects, fov_map, light_field, tone_mapping);
+    }
+    blit(panel, (0, 0), (panel_width, panel_height), &mut tcod.root, (0, panel_y), 1.0, 1.0);
+}
+
+// Draw a bar showing a value out of a maximum, with the value printed over it.
+fn render_bar(panel: &mut Offscreen, x: i32, y: i32, total_width: i32, name: &str, value: i32, maximum: i32,
+              bar_colour: (f64, f64, f64), back_colour: (f64, f64, f64)) {
+    let bar_width = if maximum > 0 { cmp::max(0, (value as f32 / maximum as f32 * total_width as f32) as i32) } else { 0 };
+    
+    panel.set_default_background(return_rgb_colour(back_colour));
+    panel.rect(x, y, total_width, 1, false, BackgroundFlag::Screen);
+    if bar_width > 0 {
+        panel.set_default_background(return_rgb_colour(bar_colour));
+        panel.rect(x, y, cmp::min(bar_width, total_width), 1, false, BackgroundFlag::Screen);
+    }
+    
+    panel.set_default_foreground(WHITE);
+    panel.print_ex(x + total_width / 2, y, BackgroundFlag::None, TextAlignment::Center, format!("{}: {}/{}", name, value, maximum));
+}
+
+// Show the whole message log, full screen, newest at the bottom. The up and down movement keys
+// scroll it; any other key closes it.
+pub fn message_history(tcod: &mut Tcod, bindings: &KeyBindings, messages: &Messages) {
+    let (screen_width, screen_height) = (tcod.root.width(), tcod.root.height());
+    let width = screen_width - 2;
+    let height = screen_height - 2;
+    
+    // Word-wrap every message into lines of its colour.
+    let mut lines: Vec<(String, (f64, f64, f64))> = vec![];
+    for &(ref message, colour) in &messages.messages {
+        let mut line = String::new();
+        for word in message.split_whitespace() {
+            if !line.is_empty() && line.len() + 1 + word.len() > width as usize {
+                lines.push((line, colour));
+                line = String::new();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push((line, colour));
+    }
+    
+    let max_scroll = cmp::max(0, lines.len() as i32 - height);
+    let mut scroll = max_scroll;
+    let mut window = Offscreen::new(screen_width, screen_height);
+    loop {
+        window.set_default_background(BLACK);
+        window.clear();
+        window.set_default_foreground(WHITE);
+        window.print_ex(screen_width / 2, 0, BackgroundFlag::None, TextAlignment::Center,
+                        "Message history (scroll with the movement keys, any other key to close)");
+        for (i, &(ref line, colour)) in lines.iter().skip(scroll as usize).take(height as usize).enumerate() {
+            window.set_default_foreground(return_rgb_colour(colour));
+            window.print_ex(1, 1 + i as i32, BackgroundFlag::None, TextAlignment::Left, line);
+        }
+        blit(&window, (0, 0), (screen_width, screen_height), &mut tcod.root, (0, 0), 1.0, 1.0);
+        tcod.flush();
+        
+        let key = tcod.wait_for_keypress();
+        match bindings.action(&key) {
+            Some(Action::Move(_, dy)) if dy != 0 => scroll = cmp::max(0, cmp::min(max_scroll, scroll + dy)),
+            _ => return,
+        }
+        if tcod.window_closed() {
+            return;
+        }
+    }
+}
+
+// The palette preview: the game's colours, at the range of lightness lighting gives them, and its
+// message colours, in the chosen palette. Enter moves on to the next palette; any other key closes
+// it, keeping the palette last shown.
+pub fn palette_preview(tcod: &mut Tcod) {
+    const SWATCH_X: i32 = 16;
+    const LIGHTNESS_STEPS: i32 = 10;
+    let swatches = [
+        ("Wall", Tile::wall().color_hsl),
+        ("Floor", Tile::empty().color_hsl),
+        ("Closed door", Tile::door(false).color_hsl),
+        ("Open door", Tile::door(true).color_hsl),
+        ("Player", COLOR_PLAYER),
+        ("Health", COLOR_HP_BAR),
+        ("Mana", COLOR_MANA_BAR),
+        ("Light", COLOR_LIGHT_BAR),
+        ("Target", COLOR_TARGET),
+    ];
+    let messages = [
+        ("Something happens.", COLOR_MSG_INFO),
+        ("Something good happens.", COLOR_MSG_GOOD),
+        ("Something bad happens.", COLOR_MSG_BAD),
+        ("Something dies.", COLOR_MSG_DEATH),
+        ("Something magical happens.", COLOR_MSG_MAGIC),
+        ("Something needs your attention.", COLOR_MSG_ALERT),
+    ];
+    
+    loop {
+        let root = &mut tcod.root;
+        root.set_default_background(BLACK);
+        root.clear();
+        root.set_default_foreground(WHITE);
+        root.print_ex(1, 1, BackgroundFlag::None, TextAlignment::Left,
+                      format!("Palette: {:?} (Enter for the next, any other key to close)", tcod.settings.palette));
+        root.print_ex(SWATCH_X, 3, BackgroundFlag::None, TextAlignment::Left, "Dark -> light");
+        for (i, &(name, colour)) in swatches.iter().enumerate() {
+            let y = 5 + i as i32 * 2;
+            root.set_default_foreground(WHITE);
+            root.print_ex(1, y, BackgroundFlag::None, TextAlignment::Left, name);
+            for step in 0..LIGHTNESS_STEPS {
+                let lightness = (step + 1) as f64 / (LIGHTNESS_STEPS + 1) as f64;
+                let swatch = return_rgb_colour((colour.0, colour.1, lightness));
+                root.set_default_background(swatch);
+                root.rect(SWATCH_X + step * 3, y, 3, 1, false, BackgroundFlag::Set);
+            }
+        }
+        let messages_y = 6 + swatches.len() as i32 * 2;
+        for (i, &(message, colour)) in messages.iter().enumerate() {
+            root.set_default_foreground(return_rgb_colour(colour));
+            root.print_ex(1, messages_y + i as i32, BackgroundFlag::None, TextAlignment::Left, message);
+        }
+        tcod.flush();
+        
+        let key = tcod.wait_for_keypress();
+        if key.code != tcod::input::KeyCode::Enter || tcod.window_closed() {
+            return;
+        }
+        tcod.settings.palette = tcod.settings.palette.next();
+    }
+}
+
+// Draw a tooltip next to the mouse, describing the visible tile under it: what it is, how much
+// light is falling on it, and anything there.
+pub fn render_tooltip(tcod: &mut Tcod, mouse: &tcod::input::Mouse, game: &Game, objects: &[Object], fov_map: &FovMap, light_field: &LightField) {
+    let (x, y) = (mouse.cx as i32, mouse.cy as i32);
+    let (map_width, map_height) = map_size(&game.map);
+    if x < 0 || x >= map_width || y < 0 || y >= map_height || !fov_map.is_in_fov(x, y) {
+        return;
+    }
+    
+    let lines = describe_tile(x, y, game, objects, fov_map, light_field, tcod.settings.tone_mapping);
+    let width = cmp::min(TOOLTIP_MAX_WIDTH, lines.iter().map(|line| line.len() as i32).max().unwrap_or(0));
+    let height = lines.len() as i32;
+    let mut window = Offscreen::new(width, height);
+    window.set_default_foreground(WHITE);
+    for (i, line) in lines.iter().enumerate() {
+        window.print_ex(0, i as i32, BackgroundFlag::None, TextAlignment::Left, line);
+    }
+    
+    // Place it below and to the right of the mouse, unless that would put it off the screen.
+    let tooltip_x = if x + 1 + width <= tcod.root.width() { x + 1 } else { x - width };
+    let tooltip_y = if y + 1 + height <= tcod.root.height() { y + 1 } else { y - height };
+    blit(&window, (0, 0), (width, height), &mut tcod.root, (tooltip_x, tooltip_y), 1.0, 0.7);
+}
+
+// Describe a map tile, one line per fact: what it is and whether it is currently visible or only
+// remembered, and if it is visible, the light falling on it (the raw illumination, and the
+// lightness it is drawn with) and everything there.
+fn describe_tile(x: i32, y: i32, game: &Game, objects: &[Object], fov_map: &FovMap, light_field: &LightField, tone_mapping: ToneMapping) -> Vec<String> {
+    let tile = &game.map[x as usize][y as usize];
+    let kind = tile.name();
+    if fov_map.is_in_fov(x, y) {
+        let illumination = light_field[x as usize][y as usize] + game.config.lighting.ambient_illumination;
+        let mut lines = vec![
+            format!("{} (visible)", kind),
+            format!("Light: {} (lightness {:.2})", illumination, tone_map(illumination, tone_mapping)),
+        ];
+        for object in objects.iter().filter(|object| object.pos() == (x, y) && object.char != ' ') {
+            lines.push(object.name.clone());
+        }
+        lines
+    } else if tile.explored {
+        let mut lines = vec![format!("{} (remembered)", kind)];
+        for ghost in game.remembered.iter().filter(|ghost| (ghost.x, ghost.y) == (x, y)) {
+            lines.push(format!("{} (last seen here)", ghost.name));
+        }
+        lines
+    } else {
+        vec!["Unexplored".into()]
+    }
+}
+
+// Look mode. Move a cursor over the map with the movement keys, and have whatever is under it
+// described in place of the message log. Any other key leaves look mode.
+pub fn look_mode(tcod: &mut Tcod, bindings: &KeyBindings, game: &Game, objects: &[Object], fov_map: &FovMap, light_field: &LightField) {
+    let (mut x, mut y) = objects[PLAYER].pos();
+    let (map_width, map_height) = map_size(&game.map);
+    let (msg_x, msg_y, msg_width, msg_height) = tcod.message_area();
+    loop {
+        tcod.root.set_default_background(BLACK);
+        tcod.root.rect(msg_x, msg_y, msg_width, msg_height, true, BackgroundFlag::Set);
+        tcod.root.set_default_foreground(return_rgb_colour(COLOR_PANEL_TEXT));
+        for (i, line) in describe_tile(x, y, game, objects, fov_map, light_field, tcod.settings.tone_mapping).iter().take(msg_height as usize).enumerate() {
+            tcod.root.print_ex(msg_x, msg_y + i as i32, BackgroundFlag::None, TextAlignment::Left, line);
+        }
+        
+        // Highlight the cursor tile on the root console, then put back what was there so that
+        // the highlight moves cleanly with the cursor.
+        let previous_background = tcod.root.get_char_background(x, y);
+        tcod.root.set_char_background(x, y, return_rgb_colour(COLOR_TARGET), BackgroundFlag::Set);
+        tcod.flush();
+        tcod.root.set_char_background(x, y, previous_background, BackgroundFlag::Set);
+        
+        let key = tcod.wait_for_keypress();
+        match bindings.action(&key) {
+            Some(Action::Move(dx, dy)) => {
+                x = cmp::max(0, cmp::min(map_width - 1, x + dx));
+                y = cmp::max(0, cmp::min(map_height - 1, y + dy));
+            }
+            _ => return,
+        }
+        if tcod.window_closed() {
+            return;
+        }
+    }
+}
+
+// Ask the player for a direction, in place of the message log. Returns the direction of the
+// movement key pressed, or None for any other key.
+pub fn ask_direction(tcod: &mut Tcod, bindings: &KeyBindings, prompt: &str) -> Option<(i32, i32)> {
+    let (msg_x, msg_y, msg_width, msg_height) = tcod.message_area();
+    tcod.root.set_default_background(BLACK);
+    tcod.root.rect(msg_x, msg_y, msg_width, msg_height, true, BackgroundFlag::Set);
+    tcod.root.set_default_foreground(return_rgb_colour(COLOR_PANEL_TEXT));
+    tcod.root.print_ex(msg_x, msg_y, BackgroundFlag::None, TextAlignment::Left, prompt);
+    tcod.flush();
+    
+    match bindings.action(&tcod.wait_for_keypress()) {
+        Some(Action::Move(dx, dy)) => Some((dx, dy)),
+        _ => None,
+    }
+}
+
+pub fn return_rgb_colour(hsl_val: (f64, f64, f64)) -> Color {
+    use tcod::colors::*;
+    use hsl::*;
+    
+    let rgb_color = HSL { h: hsl_val.0, s: hsl_val.1, l: hsl_val.2 }.to_rgb();
+    
+    let r_val = rgb_color.0;
+    let g_val = rgb_color.1;
+    let b_val = rgb_color.2;
+    
+    Color { r: r_val, g: g_val, b: b_val }
+}
@@ -0,0 +1,336 @@
+// Recording games, and playing them back to check that they come out the same.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::error::Error;
+
+use config::*;
+use content::*;
+use effects::*;
+use entities::*;
+use fov::*;
+use game::*;
+use lighting::*;
+use rendering::*;
+
+// Replay file, written at the end of each session, and the current version of its format.
+pub const REPLAY_PATH: &'static str = "replay.json";
+const REPLAY_VERSION: u64 = 1;
+pub const REPLAY_DEFAULT_SPEED: f32 = 10.0;     // Commands per second, for on-screen playback.
+
+// The seed a game was started with, the configuration it is played with (the map generated, what
+// can be seen and how monsters behave all depend on it) and every command executed since, from
+// which the game can be replayed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplayLog {
+    pub seed: u64,
+    pub config: Config,
+    pub commands: Vec<Command>,
+}
+
+// A replay file, written at the end of a session. The final state hash is checked on playback.
+#[derive(Serialize, Deserialize)]
+pub struct ReplayFile {
+    pub version: u64,
+    pub seed: u64,
+    pub config: Config,
+    pub commands: Vec<Command>,
+    pub final_hash: u64,
+}
+
+impl ReplayFile {
+    // The configuration to play the replay back with: the one it was recorded with, drawn in the
+    // tileset 'config' asks for.
+    pub fn playback_config(&self, config: &Config) -> Config {
+        let mut playback_config = self.config.clone();
+        playback_config.screen.tileset = config.screen.tileset.clone();
+        playback_config
+    }
+}
+
+// A hash of the whole game state, used to check that a replay reproduced the recorded game. This
+// is FNV-1a over the serialised state, which (unlike std's hashers) is stable between builds.
+fn state_hash(game: &Game, objects: &[Object]) -> u64 {
+    let state = (&game.map, &game.inventory, &game.flashes, &game.clock, game.depth, &game.rng, objects);
+    let json = serde_json::to_string(&state).expect("Game state can always be serialised");
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in json.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+pub fn save_replay(game: &Game, objects: &[Object], path: &str) -> Result<(), Box<dyn Error>> {
+    let replay = match game.replay {
+        Some(ref replay) => replay,
+        None => return Err("this game was not recorded from the start.".into()),
+    };
+    let replay_file = ReplayFile {
+        version: REPLAY_VERSION,
+        seed: replay.seed,
+        config: replay.config.clone(),
+        commands: replay.commands.clone(),
+        final_hash: state_hash(game, objects),
+    };
+    let mut file = File::create(path)?;
+    file.write_all(serde_json::to_string(&replay_file)?.as_bytes())?;
+    Ok(())
+}
+
+pub fn load_replay(path: &str) -> Result<ReplayFile, Box<dyn Error>> {
+    let mut json = String::new();
+    File::open(path)?.read_to_string(&mut json)?;
+    let replay_file: ReplayFile = serde_json::from_str(&json)?;
+    if replay_file.version != REPLAY_VERSION {
+        return Err(format!("Replay '{}' is format version {}, but this version of the game can only play version {}.",
+                           path, replay_file.version, REPLAY_VERSION).into());
+    }
+    if let Err(errors) = replay_file.config.validate() {
+        return Err(format!("Replay '{}' was recorded with an invalid configuration:\n    {}", path, errors.join("\n    ")).into());
+    }
+    if let Some(command) = replay_file.commands.iter().find(|command| !is_possible(command)) {
+        return Err(format!("Replay '{}' has a command that could not have been given, {:?}.", path, command).into());
+    }
+    Ok(replay_file)
+}
+
+// Whether a command could have been given in play. Moves, and opening and closing doors, are only
+// ever to a neighbouring tile (or, for moves, on the spot).
+fn is_possible(command: &Command) -> bool {
+    match *command {
+        Command::Move(dx, dy) | Command::OpenDoor(dx, dy) | Command::CloseDoor(dx, dy) => dx.abs() <= 1 && dy.abs() <= 1,
+        _ => true,
+    }
+}
+
+// Play back a recorded game, from its seed and commands, with the configuration it was recorded
+// with (see 'playback_config'), and check that it ends in the recorded state. With no window
+// ('tcod' is None), it runs as fast as possible. Otherwise it is drawn as it goes, at 'speed'
+// commands per second; '+' and '-' change the speed, and Escape stops playback. Returns whether
+// the final state matched.
+pub fn play_replay(replay_file: &ReplayFile, content: &Content, config: &Config, mut tcod: Option<&mut Tcod>, mut speed: f32) -> bool {
+    let config = replay_file.playback_config(config);
+    let (mut game, mut objects) = new_game(content, &config, replay_file.seed);
+    let mut fov_map = initialise_fov(&mut game, &mut objects);
+    let mut light_field: LightField = vec![vec![0; config.map.height as usize]; config.map.width as usize];
+    let mut light_cache = LightCache::new();
+    let mut effects = Effects::new();
+    let mut fov_recompute = true;
+    let mut budget = 0.0;
+    let mut commands = replay_file.commands.iter();
+    
+    loop {
+        if let Some(ref mut tcod) = tcod {
+            use tcod::input::{self, Event, Key};
+            
+            let cues: Vec<Cue> = game.cues.drain(..).collect();
+            if tcod.settings.effects {
+                effects.start(&cues);
+            }
+            effects.advance(1.0 / tcod.settings.fps as f64);
+            if effects.take_relight() {
+                fov_recompute = true;
+            }
+            render_all(tcod, &mut game, &objects, &fov_map, fov_recompute, &mut light_field, &mut light_cache, &effects);
+            effects.draw(&mut tcod.root, &game.map, &fov_map);
+            render_panel(tcod, &game, &objects, &fov_map, &light_field);
+            tcod.flush();
+            for object in objects.iter() {
+                object.clear(&mut tcod.con);
+            }
+            for ghost in game.remembered.iter() {
+                ghost.clear(&mut tcod.con);
+            }
+            fov_recompute = false;
+            
+            match tcod.check_for_event(input::KEY_PRESS) {
+                Some(Event::Key(Key { code: input::KeyCode::Escape, .. })) => return false,
+                Some(Event::Key(Key { printable: '+', .. })) => speed *= 2.0,
+                Some(Event::Key(Key { printable: '-', .. })) => speed /= 2.0,
+                _ => {}
+            }
+            if tcod.window_closed() {
+                return false;
+            }
+            
+            // Play as many commands this frame as the speed allows.
+            budget += speed / tcod.settings.fps as f32;
+            if budget < 1.0 {
+                continue;
+            }
+        } else {
+            // Without a window, there are no effects to show.
+            game.cues.clear();
+            budget = 1.0;
+        }
+        
+        while budget >= 1.0 {
+            budget -= 1.0;
+            match commands.next() {
+                Some(&command) => {
+                    if take_turn(command, &mut game, &mut objects, &mut fov_map, &mut [&mut light_cache]) > 0 {
+                        fov_recompute = true;
+                    }
+                }
+                None => return state_hash(&game, &objects) == replay_file.final_hash,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    
+    fn content() -> Content {
+        load_content(CONTENT_PATH).expect("the content file loads")
+    }
+    
+    // A game a few dozen commands in, walking about (or attacking) in every direction.
+    fn played_game(content: &Content, seed: u64) -> (Game, Vec<Object>) {
+        let (mut game, mut objects) = new_game(content, &Config::default(), seed);
+        let mut fov_map = initialise_fov(&mut game, &mut objects);
+        let moves = [(1, 0), (0, 1), (-1, 0), (0, -1), (1, 1), (-1, -1), (1, -1), (-1, 1)];
+        for i in 0..40 {
+            let command = match i % 5 {
+                4 => Command::Wait,
+                _ => {
+                    let (dx, dy) = moves[i % moves.len()];
+                    Command::Move(dx, dy)
+                }
+            };
+            take_turn(command, &mut game, &mut objects, &mut fov_map, &mut []);
+        }
+        (game, objects)
+    }
+    
+    // A file in the temporary directory for one test to write its replay to.
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("replay-test-{}-{}.json", std::process::id(), name));
+        path.to_str().unwrap().to_string()
+    }
+    
+    #[test]
+    fn state_hash_follows_the_state() {
+        let content = content();
+        let (game, objects) = new_game(&content, &Config::default(), 1);
+        let (same_game, same_objects) = new_game(&content, &Config::default(), 1);
+        let (other_game, other_objects) = new_game(&content, &Config::default(), 2);
+        assert_eq!(state_hash(&game, &objects), state_hash(&same_game, &same_objects));
+        assert_ne!(state_hash(&game, &objects), state_hash(&other_game, &other_objects));
+        
+        // Any turn taken changes the state, if only the clock.
+        let (mut game, mut objects) = (same_game, same_objects);
+        let before = state_hash(&game, &objects);
+        let mut fov_map = initialise_fov(&mut game, &mut objects);
+        take_turn(Command::Wait, &mut game, &mut objects, &mut fov_map, &mut []);
+        assert_ne!(state_hash(&game, &objects), before);
+    }
+    
+    #[test]
+    fn replays_reproduce_the_game() {
+        let content = content();
+        let (game, objects) = played_game(&content, 7);
+        let path = temp_path("reproduce");
+        save_replay(&game, &objects, &path).unwrap();
+        let replay_file = load_replay(&path);
+        fs::remove_file(&path).unwrap();
+        
+        let replay_file = replay_file.unwrap();
+        assert_eq!(replay_file.seed, 7);
+        assert_eq!(replay_file.commands.len(), 40);
+        assert!(play_replay(&replay_file, &content, &Config::default(), None, REPLAY_DEFAULT_SPEED));
+    }
+    
+    // Replays are played back with the configuration they were recorded with, whatever the
+    // configuration is now (except for the tileset).
+    #[test]
+    fn replays_play_with_the_recorded_configuration() {
+        let content = content();
+        let recorded_config = Config {
+            map: MapConfig { width: 40, height: 30, room_min_size: 4, room_max_size: 8, max_rooms: 6, corner_rule: "NoCutting".into() },
+            fov: FovConfig { algorithm: "Diamond".into(), torch_radius: 4, ..Default::default() },
+            lighting: LightingConfig { ambient_illumination: 500, ..Default::default() },
+            ..Default::default()
+        };
+        let (mut game, mut objects) = new_game(&content, &recorded_config, 3);
+        let mut fov_map = initialise_fov(&mut game, &mut objects);
+        for &(dx, dy) in &[(1, 0), (0, 1), (-1, 0), (0, -1), (1, 1), (-1, -1)] {
+            take_turn(Command::Move(dx, dy), &mut game, &mut objects, &mut fov_map, &mut []);
+        }
+        let path = temp_path("configuration");
+        save_replay(&game, &objects, &path).unwrap();
+        let replay_file = load_replay(&path);
+        fs::remove_file(&path).unwrap();
+        
+        let replay_file = replay_file.unwrap();
+        let config = Config {
+            screen: ScreenConfig { tileset: "Code page 437".into(), ..Default::default() },
+            ..Default::default()
+        };
+        let playback_config = replay_file.playback_config(&config);
+        assert!(playback_config.plays_like(&recorded_config));
+        assert_eq!(playback_config.screen.tileset, "Code page 437");
+        assert!(play_replay(&replay_file, &content, &config, None, REPLAY_DEFAULT_SPEED));
+    }
+    
+    #[test]
+    fn replays_that_end_elsewhere_fail() {
+        let content = content();
+        let (game, objects) = played_game(&content, 7);
+        let replay = game.replay.clone().unwrap();
+        let mut replay_file = ReplayFile {
+            version: REPLAY_VERSION,
+            seed: replay.seed,
+            config: replay.config,
+            commands: replay.commands,
+            final_hash: state_hash(&game, &objects),
+        };
+        replay_file.commands.pop();
+        assert!(!play_replay(&replay_file, &content, &Config::default(), None, REPLAY_DEFAULT_SPEED));
+        
+        replay_file.commands.push(Command::Wait);
+        replay_file.seed += 1;
+        assert!(!play_replay(&replay_file, &content, &Config::default(), None, REPLAY_DEFAULT_SPEED));
+    }
+    
+    #[test]
+    fn games_not_recorded_from_the_start_have_no_replay() {
+        let content = content();
+        let (mut game, objects) = played_game(&content, 7);
+        game.replay = None;
+        let path = temp_path("unrecorded");
+        assert!(save_replay(&game, &objects, &path).is_err());
+        assert!(fs::metadata(&path).is_err());
+    }
+    
+    #[test]
+    fn bad_replay_files_are_rejected() {
+        let content = content();
+        let (game, objects) = played_game(&content, 7);
+        let path = temp_path("rejected");
+        save_replay(&game, &objects, &path).unwrap();
+        let mut json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        
+        json["version"] = (REPLAY_VERSION + 1).into();
+        fs::write(&path, json.to_string()).unwrap();
+        let other_version = load_replay(&path);
+        
+        json["version"] = REPLAY_VERSION.into();
+        json["config"]["fov"]["algorithm"] = "no such algorithm".into();
+        fs::write(&path, json.to_string()).unwrap();
+        let bad_config = load_replay(&path);
+        
+        json["config"]["fov"]["algorithm"] = game.config.fov.algorithm.clone().into();
+        json["commands"] = serde_json::to_value(vec![Command::Wait, Command::Move(2, 0)]).unwrap();
+        fs::write(&path, json.to_string()).unwrap();
+        let long_move = load_replay(&path);
+        fs::remove_file(&path).unwrap();
+        
+        assert!(other_version.err().unwrap().to_string().contains("format version"));
+        assert!(bad_config.err().unwrap().to_string().contains("fov.algorithm 'no such algorithm'"));
+        assert!(long_move.err().unwrap().to_string().contains("Move(2, 0)"));
+    }
+}
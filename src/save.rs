@@ -0,0 +1,114 @@
+// Saving and loading games.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::error::Error;
+
+use config::*;
+use entities::*;
+use game::*;
+use map::*;
+
+// Save game file, and the current version of its format. Bump the version whenever the format
+// changes, and teach 'migrate_save' how to bring the previous version up to date.
+pub const SAVE_PATH: &'static str = "savegame";
+const SAVE_VERSION: u64 = 1;
+
+// The save file is a JSON object holding the format version alongside the game state and objects
+// list, so that the version can be checked before the rest is interpreted.
+#[derive(Serialize)]
+pub struct SaveData<'a> {
+    pub version: u64,
+    pub game: &'a Game,
+    pub objects: &'a [Object],
+}
+
+#[derive(Deserialize)]
+pub struct LoadData {
+    pub game: Game,
+    pub objects: Vec<Object>,
+}
+
+pub fn save_game(game: &Game, objects: &[Object]) -> Result<(), Box<dyn Error>> {
+    let save_data = serde_json::to_string(&SaveData { version: SAVE_VERSION, game: game, objects: objects })?;
+    let mut file = File::create(SAVE_PATH)?;
+    file.write_all(save_data.as_bytes())?;
+    Ok(())
+}
+
+// Load the saved game, to be played with the given configuration. Its map must be the size the
+// configuration asks for.
+pub fn load_game(config: &Config) -> Result<(Game, Vec<Object>), Box<dyn Error>> {
+    let mut json_save_state = String::new();
+    let mut file = File::open(SAVE_PATH)?;
+    file.read_to_string(&mut json_save_state)?;
+    
+    let save_data: serde_json::Value = serde_json::from_str(&json_save_state)?;
+    let version = match save_data.get("version").and_then(|v| v.as_u64()) {
+        Some(version) => version,
+        None => return Err(format!("'{}' is not a save file (it has no format version).", SAVE_PATH).into()),
+    };
+    let save_data = migrate_save(version, save_data)?;
+    
+    let load_data: LoadData = serde_json::from_value(save_data)?;
+    let mut game = load_data.game;
+    let (map_width, map_height) = map_size(&game.map);
+    if (map_width, map_height) != (config.map.width, config.map.height) {
+        return Err(format!("The saved game has a {}x{} map, but the configuration asks for {}x{}.",
+                           map_width, map_height, config.map.width, config.map.height).into());
+    }
+    // A game can only be replayed if it is played with one configuration throughout.
+    if game.replay.as_ref().is_some_and(|replay| !replay.config.plays_like(config)) {
+        game.replay = None;
+    }
+    game.config = config.clone();
+    Ok((game, load_data.objects))
+}
+
+// Bring a save file of an older format version up to the current version, one version at a time.
+// There is only the one version so far, so there is nothing to migrate yet.
+fn migrate_save(version: u64, save_data: serde_json::Value) -> Result<serde_json::Value, Box<dyn Error>> {
+    match version {
+        SAVE_VERSION => Ok(save_data),
+        v if v > SAVE_VERSION => Err(format!("Save file '{}' is format version {}, but this version of the game can only read up to version {}. Please update the game.",
+                                             SAVE_PATH, v, SAVE_VERSION).into()),
+        v => Err(format!("Save file '{}' is format version {}, which is not a version this game ever wrote.",
+                         SAVE_PATH, v).into()),
+    }
+}
+
+pub fn delete_save() {
+    if std::path::Path::new(SAVE_PATH).exists() {
+        if let Err(e) = std::fs::remove_file(SAVE_PATH) {
+            eprintln!("Could not delete save file '{}': {}", SAVE_PATH, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use content::*;
+    
+    fn current_save() -> serde_json::Value {
+        let content = load_content(CONTENT_PATH).expect("the content file loads");
+        let (game, objects) = new_game(&content, &Config::default(), 1);
+        serde_json::to_value(&SaveData { version: SAVE_VERSION, game: &game, objects: &objects }).unwrap()
+    }
+    
+    #[test]
+    fn current_saves_are_left_as_they_are() {
+        let save_data = current_save();
+        let migrated = migrate_save(SAVE_VERSION, save_data.clone()).unwrap();
+        assert_eq!(migrated, save_data);
+        assert!(serde_json::from_value::<LoadData>(migrated).is_ok());
+    }
+    
+    #[test]
+    fn unknown_versions_are_rejected() {
+        let too_new = migrate_save(SAVE_VERSION + 1, current_save()).unwrap_err().to_string();
+        assert!(too_new.contains("Please update the game"), "{}", too_new);
+        let too_old = migrate_save(0, current_save()).unwrap_err().to_string();
+        assert!(too_old.contains("not a version"), "{}", too_old);
+    }
+}
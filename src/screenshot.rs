@@ -0,0 +1,157 @@
+// Screenshots: the frame on screen, saved for bug reports and documentation. Each screenshot is
+// saved under the next free name, SCREENSHOT_PREFIX followed by a number, as:
+//     .png     - The window, as libtcod draws it (only when playing in a window).
+//     .txt     - The characters on screen, one line per row, as the terminal front-end shows them.
+//     .colours - The colours of each of those characters, one line per row, each cell as its
+//                foreground and background colours in hex ("rrggbb/rrggbb"), separated by spaces.
+//     .html    - The characters in their colours, as a page that can be opened in a browser.
+// The exports are taken from the frame exactly as shown: in the colours lighting gave it, in the
+// chosen palette.
+
+use std::fs::File;
+use std::io::{self, Write};
+
+use tcod::colors::Color;
+use tcod::console::*;
+
+use rendering::*;
+use terminal::*;
+
+pub const SCREENSHOT_PREFIX: &'static str = "screenshot-";
+
+// Save the frame last shown, returning the name the files were saved under.
+pub fn save_screenshot(tcod: &Tcod) -> Result<String, String> {
+    let name = (1..)
+        .map(|number| format!("{}{:03}", SCREENSHOT_PREFIX, number))
+        .find(|name| !std::path::Path::new(&format!("{}.txt", name)).exists())
+        .expect("there is always a free screenshot name");
+    
+    if let Window::Sdl(_) = tcod.window {
+        tcod::system::save_screenshot(format!("{}.png", name));
+    }
+    let frame = tcod.frame();
+    export_text(&frame, &name)
+        .and_then(|_| export_html(&frame, &name))
+        .map_err(|e| format!("Could not save screenshot '{}': {}", name, e))?;
+    Ok(name)
+}
+
+fn export_text(frame: &Offscreen, name: &str) -> io::Result<()> {
+    let mut text = File::create(format!("{}.txt", name))?;
+    let mut colours = File::create(format!("{}.colours", name))?;
+    for y in 0..frame.height() {
+        let line: String = (0..frame.width()).map(|x| printable(frame.get_char(x, y))).collect();
+        let cells: Vec<String> = (0..frame.width())
+            .map(|x| format!("{}/{}", hex(frame.get_char_foreground(x, y)), hex(frame.get_char_background(x, y))))
+            .collect();
+        writeln!(text, "{}", line.trim_end())?;
+        writeln!(colours, "{}", cells.join(" "))?;
+    }
+    Ok(())
+}
+
+// The frame as a preformatted block of text, with a span for each run of characters in the same
+// colours.
+fn export_html(frame: &Offscreen, name: &str) -> io::Result<()> {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n</head>\n", name));
+    html.push_str("<body style=\"background: #000000\">\n<pre style=\"font-family: monospace; line-height: 1\">");
+    for y in 0..frame.height() {
+        let mut run_colours = None;
+        for x in 0..frame.width() {
+            let colours = (frame.get_char_foreground(x, y), frame.get_char_background(x, y));
+            if run_colours != Some(colours) {
+                if run_colours.is_some() {
+                    html.push_str("</span>");
+                }
+                html.push_str(&format!("<span style=\"color: #{}; background: #{}\">", hex(colours.0), hex(colours.1)));
+                run_colours = Some(colours);
+            }
+            match printable(frame.get_char(x, y)) {
+                '&' => html.push_str("&amp;"),
+                '<' => html.push_str("&lt;"),
+                '>' => html.push_str("&gt;"),
+                c => html.push(c),
+            }
+        }
+        if run_colours.is_some() {
+            html.push_str("</span>");
+        }
+        html.push('\n');
+    }
+    html.push_str("</pre>\n</body>\n</html>\n");
+    File::create(format!("{}.html", name))?.write_all(html.as_bytes())
+}
+
+fn hex(colour: Color) -> String {
+    format!("{:02x}{:02x}{:02x}", colour.r, colour.g, colour.b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    
+    const ORANGE: Color = Color { r: 255, g: 128, b: 0 };
+    const NAVY: Color = Color { r: 0, g: 0, b: 128 };
+    const WHITE: Color = Color { r: 255, g: 255, b: 255 };
+    const BLACK: Color = Color { r: 0, g: 0, b: 0 };
+    
+    // A small frame: an orange '@' on navy, a wall, and a line of markup characters.
+    fn frame() -> Offscreen {
+        let mut frame = Offscreen::new(4, 2);
+        for y in 0..2 {
+            for x in 0..4 {
+                frame.put_char_ex(x, y, ' ', WHITE, BLACK);
+            }
+        }
+        frame.put_char_ex(0, 0, '@', ORANGE, NAVY);
+        frame.put_char_ex(1, 0, 219 as char, WHITE, BLACK);
+        frame.put_char_ex(0, 1, '<', WHITE, BLACK);
+        frame.put_char_ex(1, 1, '&', WHITE, BLACK);
+        frame.put_char_ex(2, 1, '>', WHITE, BLACK);
+        frame
+    }
+    
+    // Export the frame, and read back each file (text, colours and HTML), removing them after.
+    fn exported(name: &str) -> (String, String, String) {
+        let name = std::env::temp_dir().join(format!("{}{}-{}", SCREENSHOT_PREFIX, std::process::id(), name));
+        let name = name.to_str().unwrap();
+        let frame = frame();
+        export_text(&frame, name).unwrap();
+        export_html(&frame, name).unwrap();
+        let mut files = vec![];
+        for extension in &["txt", "colours", "html"] {
+            let path = format!("{}.{}", name, extension);
+            files.push(fs::read_to_string(&path).unwrap());
+            fs::remove_file(&path).unwrap();
+        }
+        (files.remove(0), files.remove(0), files.remove(0))
+    }
+    
+    #[test]
+    fn text_shows_the_characters_on_screen() {
+        let (text, _, _) = exported("text");
+        assert_eq!(text, "@\u{2588}\n<&>\n");
+    }
+    
+    #[test]
+    fn colours_are_given_for_every_cell() {
+        let (_, colours, _) = exported("colours");
+        let rows: Vec<Vec<&str>> = colours.lines().map(|line| line.split(' ').collect()).collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|row| row.len() == 4));
+        assert_eq!(rows[0][0], "ff8000/000080");
+        assert_eq!(rows[0][1], "ffffff/000000");
+    }
+    
+    #[test]
+    fn html_has_a_span_for_each_run_of_colours() {
+        let (_, _, html) = exported("html");
+        assert!(html.contains("<span style=\"color: #ff8000; background: #000080\">@</span>\
+                               <span style=\"color: #ffffff; background: #000000\">\u{2588}  </span>\n"));
+        assert!(html.contains("<span style=\"color: #ffffff; background: #000000\">&lt;&amp;&gt; </span>\n"));
+        assert_eq!(html.matches("<span").count(), html.matches("</span>").count());
+    }
+}
@@ -0,0 +1,479 @@
+// The screens the game moves between (main menu, options, playing, inventory, targeting and the
+// end of the game), and the loop that runs them.
+
+use std::cmp;
+
+use tcod::console::*;
+use tcod::colors::*;
+
+use config::*;
+use content::*;
+use effects::*;
+use entities::*;
+use fov::*;
+use game::*;
+use input::*;
+use items::*;
+use lighting::*;
+use map::*;
+use rendering::*;
+use replay::*;
+use save::*;
+use tileset::*;
+
+// The screens the game moves between. Each has its own input handling and rendering; 'run' calls
+// the current one once a frame, and moves on to whichever state it returns.
+pub enum State {
+    MainMenu,
+    Options,
+    KeyBindings,
+    InGame,
+    Inventory(InventoryUse),
+    Targeting(Targeting),
+    GameOver,
+    Victory,
+    Quit,
+}
+
+// Aiming an item: which one, where the cursor is, and why the last choice was refused, if it was.
+pub struct Targeting {
+    pub inventory_id: usize,
+    pub cursor: (i32, i32),
+    pub refusal: Option<&'static str>,
+}
+
+// A game being played on screen: the game state and objects, and everything kept alongside them
+// from frame to frame.
+pub struct Session {
+    pub game: Game,
+    pub objects: Vec<Object>,
+    pub fov_map: FovMap,
+    pub light_field: LightField,
+    pub light_cache: LightCache,
+    pub effects: Effects,
+    pub fov_recompute: bool,
+    pub input: InputState,
+}
+
+impl Session {
+    pub fn new(game: Game, objects: Vec<Object>) -> Session {
+        let (mut game, mut objects) = (game, objects);
+        let fov_map = initialise_fov(&mut game, &mut objects);
+        
+        // Generate master illumination map.
+        //
+        // This is a vector field of i32 illumination values. These are zeroed at the start of
+        // each FOV update, and then all light-sources (including the ambient illumination) are
+        // summed into it. Tiles and Objects are drawn with their 'lightness' value scaled
+        // according to this value at their position. The values are re-scaled from the native
+        // linear 0 -> 9999 to log 0.0 -> 1.0.
+        let (map_width, map_height) = map_size(&game.map);
+        let light_field: LightField = vec![vec![0; map_height as usize]; map_width as usize];
+        
+        Session {
+            game: game,
+            objects: objects,
+            fov_map: fov_map,
+            light_field: light_field,
+            light_cache: LightCache::new(),
+            effects: Effects::new(),
+            fov_recompute: true,    // Make sure that lighting is calculated on the first frame.
+            input: InputState { mouse: Default::default(), travel: None },
+        }
+    }
+    
+    // Draw the map, with any effects over it, and the GUI panel into the root console. Each call is
+    // a frame, which moves the effects on by a frame's time; they go on from frame to frame whether
+    // or not the player does anything.
+    pub fn render(&mut self, tcod: &mut Tcod) {
+        let cues: Vec<Cue> = self.game.cues.drain(..).collect();
+        if tcod.settings.effects {
+            self.effects.start(&cues);
+        } else {
+            self.effects.skip();
+        }
+        self.effects.advance(1.0 / tcod.settings.fps as f64);
+        if self.effects.take_relight() {
+            self.fov_recompute = true;
+        }
+        
+        render_all(tcod, &mut self.game, &self.objects, &self.fov_map, self.fov_recompute, &mut self.light_field, &mut self.light_cache, &self.effects);
+        self.effects.draw(&mut tcod.root, &self.game.map, &self.fov_map);
+        render_panel(tcod, &self.game, &self.objects, &self.fov_map, &self.light_field);
+        self.fov_recompute = false;
+    }
+    
+    // Display the contents of the root console, then clear all objects and remembered objects
+    // from the composition console, ready for the next frame.
+    pub fn flush(&self, tcod: &mut Tcod) {
+        tcod.flush();
+        for object in &self.objects {
+            object.clear(&mut tcod.con);
+        }
+        for ghost in &self.game.remembered {
+            ghost.clear(&mut tcod.con);
+        }
+    }
+    
+    // Carry out the player's command, then let everyone else act until the player is ready to act
+    // again. Lighting is recomputed whenever any time has elapsed. Returns the state to go on in,
+    // which is the game over or victory screen if the game has ended.
+    pub fn act(&mut self, command: Command) -> State {
+        if take_turn(command, &mut self.game, &mut self.objects, &mut self.fov_map, &mut [&mut self.light_cache, &mut self.input]) > 0 {
+            self.fov_recompute = true;
+        } else {
+            // Something is in the way, so there's no point trying to travel on.
+            self.input.travel = None;
+        }
+        
+        if !self.objects[PLAYER].alive {
+            // Permadeath: once the player has died, there is no going back to an earlier save.
+            delete_save();
+            State::GameOver
+        } else if is_victory(&self.objects) {
+            self.game.messages.add("The last of the tombs' guardians is dead. You have won!", COLOR_MSG_GOOD);
+            delete_save();
+            State::Victory
+        } else {
+            State::InGame
+        }
+    }
+    
+    // Leave the game: autosave it, unless it is over, and record it so that it can be replayed.
+    pub fn end(self) {
+        if self.objects[PLAYER].alive && !is_victory(&self.objects) {
+            if let Err(e) = save_game(&self.game, &self.objects) {
+                eprintln!("Could not save game: {}", e);
+            }
+        }
+        if let Err(e) = save_replay(&self.game, &self.objects, REPLAY_PATH) {
+            eprintln!("Could not write replay '{}': {}", REPLAY_PATH, e);
+        }
+    }
+}
+
+// Run the game from the main menu, moving from state to state, until the player quits or the
+// window is closed. Games are played with the given configuration, as changed on the options
+// screen.
+pub fn run(tcod: &mut Tcod, bindings: &mut KeyBindings, content: &Content, config: &Config) {
+    let mut config = config.clone();
+    let mut state = State::MainMenu;
+    let mut session: Option<Session> = None;
+    
+    loop {
+        if tcod.window_closed() {
+            state = State::Quit;
+        }
+        
+        // Going back to the main menu, or quitting, ends the game in progress.
+        match state {
+            State::MainMenu | State::Quit => {
+                if let Some(session) = session.take() {
+                    session.end();
+                }
+            }
+            _ => {}
+        }
+        
+        state = match state {
+            State::Quit => break,
+            State::MainMenu => main_menu(tcod, content, &config, &mut session),
+            State::Options => options_menu(tcod, bindings, &mut config),
+            State::KeyBindings => key_bindings_menu(tcod, bindings),
+            state => match session {
+                Some(ref mut session) => match state {
+                    State::InGame => in_game(tcod, bindings, session),
+                    State::Inventory(using) => choose_item(tcod, session, using),
+                    State::Targeting(targeting) => aim_item(tcod, bindings, session, targeting),
+                    State::GameOver => game_over(tcod, session, "You died!\n\nPress any key to return to the main menu."),
+                    State::Victory => game_over(tcod, session, "Victory!\n\nThe tombs are safe once more. Press any key to return to the main menu."),
+                    state => state,
+                },
+                // The game states need a game.
+                None => State::MainMenu,
+            },
+        };
+    }
+}
+
+// The main menu: start a new game, continue the saved one (if there is one), change the options,
+// or quit.
+fn main_menu(tcod: &mut Tcod, content: &Content, config: &Config, session: &mut Option<Session>) -> State {
+    let can_continue = std::path::Path::new(SAVE_PATH).exists();
+    let mut choices = vec!["New game"];
+    if can_continue {
+        choices.push("Continue");
+    }
+    choices.push("Options");
+    choices.push("Quit");
+    
+    tcod.root.set_default_background(BLACK);
+    tcod.root.clear();
+    tcod.root.set_default_foreground(return_rgb_colour(COLOR_MSG_ALERT));
+    let (screen_width, screen_height) = (tcod.root.width(), tcod.root.height());
+    tcod.root.print_ex(screen_width / 2, screen_height / 2 - 6, BackgroundFlag::None, TextAlignment::Center,
+                       "TOMBS OF THE ANCIENT KINGS");
+    render_menu("", &choices, MAIN_MENU_WIDTH, &mut tcod.root);
+    tcod.flush();
+    
+    let key = tcod.wait_for_keypress();
+    if key.code == tcod::input::KeyCode::Escape {
+        return State::Quit;
+    }
+    match menu_choice(&key, choices.len()).map(|index| choices[index]) {
+        Some("New game") => {
+            let (game, objects) = new_game(content, config, rand::random());
+            *session = Some(Session::new(game, objects));
+            State::InGame
+        }
+        Some("Continue") => {
+            match load_game(config) {
+                Ok((game, objects)) => {
+                    // Permadeath: the save is used up by continuing from it, so that a game can't
+                    // be taken back to it. It is saved afresh when the player leaves the game.
+                    delete_save();
+                    *session = Some(Session::new(game, objects));
+                    State::InGame
+                }
+                Err(e) => {
+                    let text = format!("Could not load saved game: {}\n\nDelete '{}' to start a new game.", e, SAVE_PATH);
+                    msgbox(&text, OPTIONS_WIDTH, tcod);
+                    State::MainMenu
+                }
+            }
+        }
+        Some("Options") => State::Options,
+        Some("Quit") => State::Quit,
+        _ => State::MainMenu,
+    }
+}
+
+// The options screen. Choosing an option moves it on to its next setting, which takes effect at
+// once (or for the field of view, from the next game started or continued), apart from the key
+// bindings, which have a screen of their own; any other key goes back to the main menu.
+fn options_menu(tcod: &mut Tcod, bindings: &mut KeyBindings, config: &mut Config) -> State {
+    let choices = [
+        format!("Font: {}", tcod.settings.font),
+        format!("Tileset: {}", tcod.settings.tileset.name),
+        format!("Frame rate: {} FPS", tcod.settings.fps),
+        format!("Tone mapping: {:?}", tcod.settings.tone_mapping),
+        format!("Palette: {:?}", tcod.settings.palette),
+        "Palette preview".into(),
+        format!("Effects: {}", if tcod.settings.effects { "On" } else { "Off" }),
+        format!("Minimap: {}", if tcod.settings.minimap { "On" } else { "Off" }),
+        format!("Keys: {}", bindings.config.preset),
+        "Key bindings".into(),
+        format!("Field of view: {}", config.fov.algorithm),
+        "Back".into(),
+    ];
+    
+    tcod.root.set_default_background(BLACK);
+    tcod.root.clear();
+    match menu("Options\n", &choices, OPTIONS_WIDTH, tcod) {
+        Some(0) => {
+            let fonts = available_fonts();
+            let next = fonts.iter().position(|font| *font == tcod.settings.font).map_or(0, |i| i + 1);
+            if let Some(font) = fonts.get(next % cmp::max(1, fonts.len())) {
+                tcod.settings.font = font.clone();
+                tcod.reopen();
+            }
+        }
+        Some(1) => {
+            // Only tilesets whose sheets can be found are offered. Tiles drawn with the old
+            // tileset are cleared away, to be drawn again with the new one.
+            let tilesets: Vec<&Tileset> = tcod.tilesets.iter().filter(|tileset| tileset.is_available()).collect();
+            let next = tilesets.iter().position(|tileset| tileset.name == tcod.settings.tileset.name).map_or(0, |i| i + 1);
+            tcod.settings.tileset = tilesets[next % tilesets.len()].clone();
+            tcod.reopen();
+            tcod.con.clear();
+        }
+        Some(2) => {
+            let next = FPS_CHOICES.iter().position(|&fps| fps == tcod.settings.fps).map_or(0, |i| i + 1);
+            tcod.settings.fps = FPS_CHOICES[next % FPS_CHOICES.len()];
+            tcod::system::set_fps(tcod.settings.fps);
+        }
+        Some(3) => tcod.settings.tone_mapping = tcod.settings.tone_mapping.next(),
+        Some(4) => tcod.settings.palette = tcod.settings.palette.next(),
+        Some(5) => palette_preview(tcod),
+        Some(6) => tcod.settings.effects = !tcod.settings.effects,
+        Some(7) => tcod.settings.minimap = !tcod.settings.minimap,
+        Some(8) => {
+            let next = KEY_PRESETS.iter().position(|&preset| preset == bindings.config.preset).map_or(0, |i| i + 1);
+            match bindings.with_preset(KEY_PRESETS[next % KEY_PRESETS.len()]) {
+                Ok(new_bindings) => *bindings = new_bindings,
+                Err(errors) => msgbox(&errors.join("\n"), OPTIONS_WIDTH, tcod),
+            }
+        }
+        Some(9) => return State::KeyBindings,
+        Some(10) => config.fov.algorithm = config.fov.algorithm().next().name().into(),
+        _ => return State::MainMenu,
+    }
+    State::Options
+}
+
+// The key bindings screen: every action, with the keys bound to it. Choosing one asks for a key to
+// bind to it, which is kept as a change to the preset; any other key goes back to the options.
+fn key_bindings_menu(tcod: &mut Tcod, bindings: &mut KeyBindings) -> State {
+    let choices: Vec<String> = ACTIONS.iter()
+        .map(|&action| format!("{:<28}{}", action.describe(), bindings.keys_for(action).join(", ")))
+        .collect();
+    
+    tcod.root.set_default_background(BLACK);
+    tcod.root.clear();
+    let action = match menu("Key bindings\n\nChoose an action to bind a key to it.\n", &choices, KEY_BINDINGS_WIDTH, tcod) {
+        Some(index) => ACTIONS[index],
+        None => return State::Options,
+    };
+    
+    let prompt = format!("Press the key for '{}', or Escape to cancel.", action.describe());
+    let options: &[&str] = &[];
+    render_menu(&prompt, options, KEY_BINDINGS_WIDTH, &mut tcod.root);
+    tcod.flush();
+    // Modifiers are part of the key they are held down with, not keys of their own.
+    let key = loop {
+        let key = tcod.wait_for_keypress();
+        match key.code {
+            tcod::input::KeyCode::Shift | tcod::input::KeyCode::Control | tcod::input::KeyCode::Alt => {}
+            _ => break key,
+        }
+    };
+    if key.code == tcod::input::KeyCode::Escape {
+        return State::KeyBindings;
+    }
+    match KeySpec::name_of(&key) {
+        Some(name) => match bindings.rebind(&name, action) {
+            Ok(new_bindings) => *bindings = new_bindings,
+            Err(errors) => msgbox(&errors.join("\n"), KEY_BINDINGS_WIDTH, tcod),
+        },
+        None => msgbox("That key can't be bound.", KEY_BINDINGS_WIDTH, tcod),
+    }
+    State::KeyBindings
+}
+
+// Playing: draw the world, and handle the player's input.
+fn in_game(tcod: &mut Tcod, bindings: &KeyBindings, session: &mut Session) -> State {
+    session.render(tcod);
+    render_tooltip(tcod, &session.input.mouse, &session.game, &session.objects, &session.fov_map, &session.light_field);
+    session.flush(tcod);
+    
+    let action = handle_input(tcod, bindings, &mut session.game, &session.objects, &session.fov_map, &session.light_field, &mut session.input);
+    match action {
+        PlayerAction::Exit => State::MainMenu,
+        PlayerAction::DidntTakeTurn => State::InGame,
+        PlayerAction::Inventory(using) => State::Inventory(using),
+        PlayerAction::Act(command) => session.act(command),
+    }
+}
+
+// Choosing an item from the inventory menu, to drop it or to use it (aiming it first, if it needs
+// a target). Any key that isn't an item's letter goes back to the game.
+fn choose_item(tcod: &mut Tcod, session: &mut Session, using: InventoryUse) -> State {
+    let header = match using {
+        InventoryUse::Use => "Press the key next to an item to use it, or any other to cancel.\n",
+        InventoryUse::Drop => "Press the key next to an item to drop it, or any other to cancel.\n",
+    };
+    session.render(tcod);
+    render_menu(header, &inventory_options(&session.game.inventory), INVENTORY_WIDTH, &mut tcod.root);
+    session.flush(tcod);
+    
+    let key = tcod.wait_for_keypress();
+    match (menu_choice(&key, session.game.inventory.len()), using) {
+        (Some(inventory_id), InventoryUse::Drop) => session.act(Command::DropItem(inventory_id)),
+        (Some(inventory_id), InventoryUse::Use) => {
+            if item_targeting(&session.game.inventory[inventory_id]).is_some() {
+                let cursor = session.objects[PLAYER].pos();
+                State::Targeting(Targeting { inventory_id: inventory_id, cursor: cursor, refusal: None })
+            } else {
+                session.act(Command::UseItem(inventory_id, None))
+            }
+        }
+        (None, _) => State::InGame,
+    }
+}
+
+// Aiming an item. Move a cursor over the map with the movement keys or the mouse, and use the item
+// on the tile chosen with Enter or a left-click. The tile must be in the player's FOV and, if the
+// item has a range, within that range of the player; some items also need a monster there. Escape
+// or a right-click cancels.
+fn aim_item(tcod: &mut Tcod, bindings: &KeyBindings, session: &mut Session, targeting: Targeting) -> State {
+    use tcod::input::{self, Event, Key};
+    use tcod::input::KeyCode::*;
+    
+    let mut targeting = targeting;
+    let (prompt, max_range, needs_monster) = item_targeting(&session.game.inventory[targeting.inventory_id])
+        .expect("only items that need a target are aimed");
+    let (mut x, mut y) = targeting.cursor;
+    let (map_width, map_height) = map_size(&session.game.map);
+    let (msg_x, msg_y, msg_width, msg_height) = tcod.message_area();
+    
+    // Show the prompt (or why the last choice was refused) in place of the message log, and
+    // highlight the cursor tile.
+    session.render(tcod);
+    tcod.root.set_default_background(BLACK);
+    tcod.root.rect(msg_x, msg_y, msg_width, msg_height, true, BackgroundFlag::Set);
+    tcod.root.set_default_foreground(return_rgb_colour(COLOR_MSG_ALERT));
+    tcod.root.print_rect(msg_x, msg_y, msg_width, msg_height, targeting.refusal.unwrap_or(prompt));
+    tcod.root.set_char_background(x, y, return_rgb_colour(COLOR_TARGET), BackgroundFlag::Set);
+    session.flush(tcod);
+    
+    let mut chosen = false;
+    let (mut dx, mut dy) = (0, 0);
+    match tcod.check_for_event(input::MOUSE | input::KEY_PRESS) {
+        Some(Event::Mouse(mouse)) => {
+            session.input.mouse = mouse;
+            let (mouse_x, mouse_y) = (mouse.cx as i32, mouse.cy as i32);
+            if mouse_x >= 0 && mouse_x < map_width && mouse_y >= 0 && mouse_y < map_height {
+                x = mouse_x;
+                y = mouse_y;
+                chosen = mouse.lbutton_pressed;
+            }
+            if mouse.rbutton_pressed {
+                return State::InGame;
+            }
+        }
+        Some(Event::Key(key)) => {
+            match (key, bindings.action(&key)) {
+                (Key { code: Escape, .. }, _) | (_, Some(Action::Exit)) => return State::InGame,
+                (Key { code: Enter, .. }, _) => chosen = true,
+                (_, Some(Action::Move(move_x, move_y))) => {
+                    dx = move_x;
+                    dy = move_y;
+                }
+                _ => {},
+            }
+        }
+        None => {},
+    }
+    
+    x = cmp::max(0, cmp::min(map_width - 1, x + dx));
+    y = cmp::max(0, cmp::min(map_height - 1, y + dy));
+    targeting.cursor = (x, y);
+    
+    if chosen {
+        let (player_x, player_y) = session.objects[PLAYER].pos();
+        let in_fov = session.fov_map.is_in_fov(x, y);
+        let in_range = max_range.is_none_or(|range| {
+            ((((x - player_x).pow(2) + (y - player_y).pow(2)) as f32).sqrt()) <= range
+        });
+        let monster_there = session.objects.iter().enumerate()
+            .any(|(id, object)| id != PLAYER && object.pos() == (x, y) && object.fighter.is_some());
+        if !in_fov || !in_range {
+            targeting.refusal = Some("That tile is out of sight or out of range.");
+        } else if needs_monster && !monster_there {
+            targeting.refusal = Some("There is nobody there.");
+        } else {
+            return session.act(Command::UseItem(targeting.inventory_id, Some((x, y))));
+        }
+    }
+    State::Targeting(targeting)
+}
+
+// The game has ended, one way or the other. Show the final state of the world with a closing
+// message over it; any key goes back to the main menu.
+fn game_over(tcod: &mut Tcod, session: &mut Session, text: &str) -> State {
+    session.render(tcod);
+    render_menu(text, &[] as &[&str], ENDING_WIDTH, &mut tcod.root);
+    session.flush(tcod);
+    tcod.wait_for_keypress();
+    State::MainMenu
+}
@@ -0,0 +1,266 @@
+// The terminal front-end: shows the frames composed in libtcod's consoles in a plain terminal,
+// using ANSI colour codes, and reads keys and the mouse from it, so that the game can be played
+// without a window (over SSH, say). It draws with the ASCII tileset, as a terminal has no sprites.
+
+use std::cmp;
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::{cursor, event, style, terminal, QueueableCommand};
+use crossterm::event::{Event as TerminalEvent, KeyCode as TerminalKeyCode, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind};
+
+use tcod::colors::{self, Color};
+use tcod::console::*;
+use tcod::input::{Event, Key, KeyCode, Mouse};
+
+// How colours are sent to the terminal: as 24-bit RGB, which most terminals understand, or as the
+// nearest of the 256 standard colours, for those that don't.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TerminalColours {
+    TrueColour,
+    Ansi256,
+}
+
+impl TerminalColours {
+    // Terminals that understand 24-bit colour say so in COLORTERM.
+    pub fn detect() -> TerminalColours {
+        match std::env::var("COLORTERM") {
+            Ok(ref value) if value == "truecolor" || value == "24bit" => TerminalColours::TrueColour,
+            _ => TerminalColours::Ansi256,
+        }
+    }
+}
+
+// A screen cell: its character, and its foreground and background colours.
+type Cell = (char, Color, Color);
+
+// The terminal, in raw mode on the alternate screen while the game has it, and what it is showing,
+// so that only the cells that change need be sent each frame.
+pub struct Terminal {
+    out: Stdout,
+    colours: TerminalColours,
+    shown: Vec<Cell>,       // Empty when the whole screen must be sent again.
+    mouse: Mouse,
+    closed: bool,           // Whether input has stopped coming, as when the connection drops.
+    last_frame: Instant,
+}
+
+impl Terminal {
+    // Take over the terminal, which must be at least 'width' by 'height' characters.
+    pub fn new(width: i32, height: i32, colours: TerminalColours) -> Result<Terminal, String> {
+        let (columns, rows) = terminal::size()
+            .map_err(|e| format!("Could not find the size of the terminal: {}", e))?;
+        if (columns as i32) < width || (rows as i32) < height {
+            return Err(format!("The terminal must be at least {} by {} characters, but is {} by {}.", width, height, columns, rows));
+        }
+        
+        let mut out = io::stdout();
+        terminal::enable_raw_mode()
+            .and_then(|_| out.queue(terminal::EnterAlternateScreen).map(|_| ()))
+            .and_then(|_| out.queue(cursor::Hide).map(|_| ()))
+            .and_then(|_| out.queue(event::EnableMouseCapture).map(|_| ()))
+            .and_then(|_| out.flush())
+            .map_err(|e| format!("Could not set up the terminal: {}", e))?;
+        Ok(Terminal { out: out, colours: colours, shown: vec![], mouse: Default::default(), closed: false, last_frame: Instant::now() })
+    }
+    
+    // Show a frame, sending only the cells that differ from what is already on screen, then wait
+    // out the rest of the frame, so that frames are shown no more than 'fps' times a second.
+    pub fn draw(&mut self, console: &Offscreen, fps: i32) -> io::Result<()> {
+        let (width, height) = (console.width(), console.height());
+        let redraw = self.shown.len() != (width * height) as usize;
+        if redraw {
+            self.shown = vec![(' ', colors::BLACK, colors::BLACK); (width * height) as usize];
+            self.out.queue(terminal::Clear(terminal::ClearType::All))?;
+        }
+        
+        // Colours are only sent when they change, as they take up most of what is sent.
+        let mut colours = None;
+        let mut next_position = None;
+        for y in 0..height {
+            for x in 0..width {
+                let cell = (console.get_char(x, y), console.get_char_foreground(x, y), console.get_char_background(x, y));
+                let index = (y * width + x) as usize;
+                if !redraw && self.shown[index] == cell {
+                    continue;
+                }
+                self.shown[index] = cell;
+                
+                if next_position != Some((x, y)) {
+                    self.out.queue(cursor::MoveTo(x as u16, y as u16))?;
+                }
+                if colours != Some((cell.1, cell.2)) {
+                    let (foreground, background) = (self.colour(cell.1), self.colour(cell.2));
+                    self.out.queue(style::SetForegroundColor(foreground))?;
+                    self.out.queue(style::SetBackgroundColor(background))?;
+                    colours = Some((cell.1, cell.2));
+                }
+                self.out.queue(style::Print(printable(cell.0)))?;
+                next_position = Some((x + 1, y));
+            }
+        }
+        self.out.flush()?;
+        
+        let frame = Duration::from_secs(1) / cmp::max(1, fps) as u32;
+        let elapsed = self.last_frame.elapsed();
+        if elapsed < frame {
+            std::thread::sleep(frame - elapsed);
+        }
+        self.last_frame = Instant::now();
+        Ok(())
+    }
+    
+    // The next key pressed, waiting for one.
+    pub fn wait_for_keypress(&mut self) -> Key {
+        loop {
+            if let Some(Event::Key(key)) = self.next_event(None) {
+                return key;
+            }
+            if self.closed {
+                return Key { code: KeyCode::Escape, ..Default::default() };
+            }
+        }
+    }
+    
+    // The next key press or mouse event, if there is one waiting.
+    pub fn check_for_event(&mut self) -> Option<Event> {
+        self.next_event(Some(Duration::from_millis(0)))
+    }
+    
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+    
+    // Read an event from the terminal, waiting up to 'timeout' for one (or for ever, if None), and
+    // turn it into libtcod's equivalent. Events with no equivalent are dropped.
+    fn next_event(&mut self, timeout: Option<Duration>) -> Option<Event> {
+        if self.closed {
+            return None;
+        }
+        let ready = match timeout {
+            Some(timeout) => event::poll(timeout),
+            None => Ok(true),
+        };
+        let read = match ready {
+            Ok(true) => event::read(),
+            Ok(false) => return None,
+            Err(e) => Err(e),
+        };
+        match read {
+            Ok(TerminalEvent::Key(key_event)) if key_event.kind != KeyEventKind::Release => {
+                key_from(key_event.code, key_event.modifiers).map(Event::Key)
+            }
+            Ok(TerminalEvent::Mouse(mouse_event)) => {
+                self.mouse.cx = mouse_event.column as isize;
+                self.mouse.cy = mouse_event.row as isize;
+                self.mouse.lbutton_pressed = mouse_event.kind == MouseEventKind::Down(MouseButton::Left);
+                Some(Event::Mouse(self.mouse))
+            }
+            Ok(TerminalEvent::Resize(..)) => {
+                // Whatever was on screen may have been lost.
+                self.shown.clear();
+                None
+            }
+            Ok(_) => None,
+            Err(_) => {
+                self.closed = true;
+                None
+            }
+        }
+    }
+    
+    fn colour(&self, colour: Color) -> style::Color {
+        match self.colours {
+            TerminalColours::TrueColour => style::Color::Rgb { r: colour.r, g: colour.g, b: colour.b },
+            TerminalColours::Ansi256 => style::Color::AnsiValue(ansi_256(colour)),
+        }
+    }
+}
+
+// Give the terminal back as it was found.
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.out.queue(style::ResetColor)
+            .and_then(|out| out.queue(event::DisableMouseCapture))
+            .and_then(|out| out.queue(cursor::Show))
+            .and_then(|out| out.queue(terminal::LeaveAlternateScreen))
+            .and_then(|out| out.flush());
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// The top half of code page 437, as libtcod's fonts draw character codes 128 to 255.
+const CP437_UPPER: &'static str = "ÇüéâäàåçêëèïîìÄÅÉæÆôöòûùÿÖÜ¢£¥₧ƒáíóúñÑªº¿⌐¬½¼¡«»░▒▓│┤╡╢╖╕╣║╗╝╜╛┐\
+                                   └┴┬├─┼╞╟╚╔╩╦╠═╬╧╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀αßΓπΣσµτΦΘΩδ∞φε∩≡±≥≤⌠⌡÷≈°∙·√ⁿ²■ ";
+
+// The character the terminal should show for one drawn on a console. Codes above 127 are drawn
+// from code page 437, as libtcod would; control characters would upset the terminal, so are shown
+// as blanks.
+pub fn printable(c: char) -> char {
+    match c as u32 {
+        128..=255 => CP437_UPPER.chars().nth(c as usize - 128).unwrap_or(' '),
+        _ if c.is_control() => ' ',
+        _ => c,
+    }
+}
+
+// The libtcod key for a terminal key, if it has one. Terminals can't tell the numeric keypad from
+// the other keys, so the numpad key preset won't work in one.
+fn key_from(code: TerminalKeyCode, modifiers: KeyModifiers) -> Option<Key> {
+    let key_code = match code {
+        TerminalKeyCode::Char(' ') => KeyCode::Spacebar,
+        TerminalKeyCode::Char(_) => KeyCode::Char,
+        TerminalKeyCode::Esc => KeyCode::Escape,
+        TerminalKeyCode::Enter => KeyCode::Enter,
+        TerminalKeyCode::Tab => KeyCode::Tab,
+        TerminalKeyCode::Backspace => KeyCode::Backspace,
+        TerminalKeyCode::Up => KeyCode::Up,
+        TerminalKeyCode::Down => KeyCode::Down,
+        TerminalKeyCode::Left => KeyCode::Left,
+        TerminalKeyCode::Right => KeyCode::Right,
+        TerminalKeyCode::Home => KeyCode::Home,
+        TerminalKeyCode::End => KeyCode::End,
+        TerminalKeyCode::PageUp => KeyCode::PageUp,
+        TerminalKeyCode::PageDown => KeyCode::PageDown,
+        TerminalKeyCode::F(1) => KeyCode::F1,
+        TerminalKeyCode::F(12) => KeyCode::F12,
+        _ => return None,
+    };
+    let (alt, ctrl) = (modifiers.contains(KeyModifiers::ALT), modifiers.contains(KeyModifiers::CONTROL));
+    Some(Key {
+        code: key_code,
+        printable: if let TerminalKeyCode::Char(c) = code { c } else { '\0' },
+        pressed: true,
+        shift: modifiers.contains(KeyModifiers::SHIFT),
+        alt: alt,
+        left_alt: alt,
+        ctrl: ctrl,
+        left_ctrl: ctrl,
+        ..Default::default()
+    })
+}
+
+// The nearest of the 256 standard terminal colours: either in the 6x6x6 colour cube, or on the
+// ramp of greys.
+fn ansi_256(colour: Color) -> u8 {
+    const CUBE_LEVELS: [i32; 6] = [0, 95, 135, 175, 215, 255];
+    let nearest_level = |value: u8| {
+        (0..6).min_by_key(|&i| (CUBE_LEVELS[i] - value as i32).abs()).unwrap()
+    };
+    let (r, g, b) = (nearest_level(colour.r), nearest_level(colour.g), nearest_level(colour.b));
+    let cube = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+    
+    let average = (colour.r as i32 + colour.g as i32 + colour.b as i32) / 3;
+    let grey_index = ((average - 3) / 10).clamp(0, 23);
+    let grey_level = 8 + grey_index * 10;
+    
+    let distance = |(r, g, b): (i32, i32, i32)| {
+        let (dr, dg, db) = (r - colour.r as i32, g - colour.g as i32, b - colour.b as i32);
+        dr * dr + dg * dg + db * db
+    };
+    if distance((grey_level, grey_level, grey_level)) < distance(cube) {
+        232 + grey_index as u8
+    } else {
+        16 + (36 * r + 6 * g + b) as u8
+    }
+}
@@ -0,0 +1,224 @@
+// Tilesets: which sprite in a font sheet each kind of tile and object is drawn with. The ASCII
+// tileset, built in, draws tiles as coloured backgrounds and objects as their glyphs, in the font
+// chosen on the options screen. Others are loaded from tilesets.ron, and draw whatever they have
+// sprites for from their own sheet, falling back to ASCII for anything they don't.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+
+use map::*;
+
+pub const TILESETS_PATH: &'static str = "tilesets.ron";
+pub const ASCII_TILESET: &'static str = "ASCII";
+
+// Text is drawn from the same sheet as the sprites, so a sheet must hold at least the 256
+// characters of code page 437.
+const MIN_SHEET_CELLS: u32 = 256;
+// Sprite N is drawn as character code N, which libtcod keeps to 16 bits; and the codes set aside
+// for UTF-16 surrogates are not characters at all, so can't be sprites.
+const MAX_SHEET_CELLS: u32 = 0x10000;
+const SURROGATES: std::ops::RangeInclusive<u32> = 0xD800..=0xDFFF;
+
+// The kinds of map tile a tileset can give sprites to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum TileKind {
+    Wall,
+    Floor,
+    ClosedDoor,
+    OpenDoor,
+}
+
+impl TileKind {
+    pub fn of(tile: &Tile) -> TileKind {
+        match (tile.door, tile.blocked) {
+            (true, true) => TileKind::ClosedDoor,
+            (true, false) => TileKind::OpenDoor,
+            (false, true) => TileKind::Wall,
+            (false, false) => TileKind::Floor,
+        }
+    }
+}
+
+// A tileset, as defined in TILESETS_PATH. Its sheet is laid out in rows of 'columns' cells, with
+// code page 437 in the first 256 cells, and sprites numbered along the rows from 0, so that
+// sprite N is drawn as character code N.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tileset {
+    pub name: String,
+    pub sheet: String,                      // Empty for the ASCII tileset, which uses the font.
+    #[serde(default)]
+    pub columns: u32,
+    #[serde(default)]
+    pub rows: u32,
+    #[serde(default)]
+    pub tiles: HashMap<TileKind, u32>,      // Sprites for map tiles, by kind.
+    #[serde(default)]
+    pub objects: HashMap<String, u32>,      // Sprites for objects, by name.
+    #[serde(default)]
+    pub glyphs: HashMap<char, u32>,         // Sprites for any other object, by its ASCII glyph.
+}
+
+impl Tileset {
+    pub fn ascii() -> Tileset {
+        Tileset {
+            name: ASCII_TILESET.into(),
+            sheet: String::new(),
+            columns: 0,
+            rows: 0,
+            tiles: HashMap::new(),
+            objects: HashMap::new(),
+            glyphs: HashMap::new(),
+        }
+    }
+    
+    // Whether the tileset can be used: the ASCII tileset always can, and others if their sheet is
+    // there to be loaded.
+    pub fn is_available(&self) -> bool {
+        self.sheet.is_empty() || std::path::Path::new(&self.sheet).exists()
+    }
+    
+    // The sprite to draw a map tile with, or None to draw it as just its background colour.
+    pub fn tile_glyph(&self, tile: &Tile) -> Option<char> {
+        self.tiles.get(&TileKind::of(tile)).map(|&sprite| sprite_glyph(sprite))
+    }
+    
+    // The sprite to draw an object (or the memory of one) with, by its name or else its glyph,
+    // falling back to the glyph itself.
+    pub fn object_glyph(&self, name: &str, glyph: char) -> char {
+        self.objects.get(name)
+            .or_else(|| self.glyphs.get(&glyph))
+            .map_or(glyph, |&sprite| sprite_glyph(sprite))
+    }
+    
+    fn validate(&self, names: &mut HashSet<String>, errors: &mut Vec<String>) {
+        let what = format!("tileset '{}'", self.name);
+        if self.name.is_empty() {
+            errors.push("tileset: name must not be empty".into());
+        } else if self.name == ASCII_TILESET {
+            errors.push(format!("{}: the name is taken by the built-in tileset", what));
+        } else if !names.insert(self.name.clone()) {
+            errors.push(format!("{}: name is defined more than once", what));
+        }
+        if self.sheet.is_empty() {
+            errors.push(format!("{}: sheet must be given", what));
+        }
+        let cells = match self.columns.checked_mul(self.rows) {
+            Some(cells) if cells < MIN_SHEET_CELLS => {
+                errors.push(format!("{}: the sheet must have at least {} cells, got {} columns by {} rows",
+                                    what, MIN_SHEET_CELLS, self.columns, self.rows));
+                cells
+            }
+            Some(cells) if cells <= MAX_SHEET_CELLS => cells,
+            _ => {
+                errors.push(format!("{}: the sheet must have at most {} cells, got {} columns by {} rows",
+                                    what, MAX_SHEET_CELLS, self.columns, self.rows));
+                MAX_SHEET_CELLS
+            }
+        };
+        let sprites = self.tiles.values().chain(self.objects.values()).chain(self.glyphs.values());
+        for &sprite in sprites {
+            if sprite >= cells {
+                errors.push(format!("{}: sprite {} is not on the sheet, which has {} cells", what, sprite, cells));
+            } else if SURROGATES.contains(&sprite) {
+                errors.push(format!("{}: sprite {} can't be used, as sprites {} to {} have no character code",
+                                    what, sprite, SURROGATES.start(), SURROGATES.end()));
+            }
+        }
+    }
+}
+
+fn sprite_glyph(sprite: u32) -> char {
+    std::char::from_u32(sprite).expect("sprites are checked to have character codes when loaded")
+}
+
+// Load and validate the tilesets, with the ASCII tileset first. The file is optional; without it,
+// only the ASCII tileset is offered.
+pub fn load_tilesets(path: &str) -> Result<Vec<Tileset>, String> {
+    let mut tilesets = vec![Tileset::ascii()];
+    if !std::path::Path::new(path).exists() {
+        return Ok(tilesets);
+    }
+    
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|e| format!("Could not read tilesets file '{}': {}", path, e))?;
+    let loaded: Vec<Tileset> = ron::de::from_str(&text)
+        .map_err(|e| format!("Could not parse tilesets file '{}': {}", path, e))?;
+    
+    let mut names = HashSet::new();
+    let mut errors = vec![];
+    for tileset in &loaded {
+        tileset.validate(&mut names, &mut errors);
+    }
+    if !errors.is_empty() {
+        return Err(format!("Invalid tilesets file '{}':\n    {}", path, errors.join("\n    ")));
+    }
+    
+    tilesets.extend(loaded);
+    Ok(tilesets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // The width and height of a PNG image, from its header.
+    fn png_size(path: &str) -> (u32, u32) {
+        let mut header = [0; 24];
+        File::open(path).and_then(|mut file| file.read_exact(&mut header)).unwrap();
+        assert_eq!(&header[1..4], b"PNG", "{} is not a PNG image", path);
+        let number = |at: usize| header[at..at + 4].iter().fold(0, |n, &byte| n << 8 | byte as u32);
+        (number(16), number(20))
+    }
+    
+    #[test]
+    fn listed_tilesets_load() {
+        let tilesets = load_tilesets(TILESETS_PATH).unwrap();
+        assert!(tilesets.len() > 1);
+        for tileset in tilesets.iter().skip(1) {
+            assert!(tileset.is_available(), "the sheet of {} is missing", tileset.name);
+            let (width, height) = png_size(&tileset.sheet);
+            assert!(width % tileset.columns == 0 && height % tileset.rows == 0,
+                    "the sheet of {} is {}x{} pixels, which doesn't divide into {} columns by {} rows",
+                    tileset.name, width, height, tileset.columns, tileset.rows);
+        }
+    }
+    
+    #[test]
+    fn sprites_off_the_sheet_are_rejected() {
+        let mut tileset = Tileset::ascii();
+        tileset.name = "Small".into();
+        tileset.sheet = "small.png".into();
+        tileset.columns = 16;
+        tileset.rows = 16;
+        tileset.tiles.insert(TileKind::Wall, 256);
+        let mut errors = vec![];
+        tileset.validate(&mut HashSet::new(), &mut errors);
+        assert_eq!(errors, vec!["tileset 'Small': sprite 256 is not on the sheet, which has 256 cells".to_string()]);
+    }
+    
+    // Sheets too big to number every sprite as a character are rejected, as are the sprites that
+    // would be drawn as surrogate codes, rather than failing when drawn.
+    #[test]
+    fn sprites_must_have_character_codes() {
+        let mut tileset = Tileset::ascii();
+        tileset.name = "Huge".into();
+        tileset.sheet = "huge.png".into();
+        tileset.columns = 0x10000;
+        tileset.rows = 0x10000;
+        let mut errors = vec![];
+        tileset.validate(&mut HashSet::new(), &mut errors);
+        assert_eq!(errors, vec!["tileset 'Huge': the sheet must have at most 65536 cells, got 65536 columns by 65536 rows".to_string()]);
+        
+        tileset.columns = 256;
+        tileset.rows = 256;
+        tileset.glyphs.insert('#', 0xD800);
+        tileset.objects.insert("orc".into(), 0xD7FF);
+        let mut errors = vec![];
+        tileset.validate(&mut HashSet::new(), &mut errors);
+        assert_eq!(errors, vec!["tileset 'Huge': sprite 55296 can't be used, as sprites 55296 to 57343 have no character code".to_string()]);
+    }
+}
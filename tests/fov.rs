@@ -0,0 +1,153 @@
+// Field of view tests, on small maps built by hand or at random.
+
+extern crate roguelike;
+
+use roguelike::fov::*;
+use roguelike::map::*;
+
+const ALGORITHMS: [FovAlgorithm; 4] = [FovAlgorithm::Shadowcasting, FovAlgorithm::Permissive,
+                                       FovAlgorithm::Diamond, FovAlgorithm::Raycasting];
+
+// A map of floor, walled in, with walls wherever 'walls' puts them.
+fn make_test_map(width: i32, height: i32, walls: &[(i32, i32)]) -> Map {
+    let mut map = Map::new(width, height, Tile::empty());
+    for x in 0..width {
+        for y in 0..height {
+            if x == 0 || y == 0 || x == width - 1 || y == height - 1 || walls.contains(&(x, y)) {
+                map.set_tile(x, y, Tile::wall());
+            }
+        }
+    }
+    map
+}
+
+// A walled-in map with about one tile in 'one_in' a wall, placed by a simple, seeded generator so
+// that failures can be reproduced.
+fn make_random_map(width: i32, height: i32, one_in: u64, seed: u64) -> Map {
+    let mut state = seed;
+    let mut walls = vec![];
+    for x in 0..width {
+        for y in 0..height {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            if (state >> 33).is_multiple_of(one_in) {
+                walls.push((x, y));
+            }
+        }
+    }
+    make_test_map(width, height, &walls)
+}
+
+fn fov_from(map: &Map, x: i32, y: i32, radius: i32, light_walls: bool, algorithm: FovAlgorithm) -> FovMap {
+    let (width, height) = map_size(map);
+    let mut fov_map = FovMap::new(width, height);
+    fov_map.compute_fov(map, x, y, radius, light_walls, algorithm);
+    fov_map
+}
+
+fn floor_tiles(map: &Map) -> Vec<(i32, i32)> {
+    let (width, height) = map_size(map);
+    let mut tiles = vec![];
+    for x in 0..width {
+        for y in 0..height {
+            if !map[x as usize][y as usize].block_sight {
+                tiles.push((x, y));
+            }
+        }
+    }
+    tiles
+}
+
+// Check that any floor tile seen from another floor tile can see it back.
+fn assert_symmetric(algorithm: FovAlgorithm, width: i32, height: i32) {
+    for seed in 0..4 {
+        let map = make_random_map(width, height, 4, seed);
+        let tiles = floor_tiles(&map);
+        let views: Vec<FovMap> = tiles.iter().map(|&(x, y)| fov_from(&map, x, y, 0, true, algorithm)).collect();
+        for (i, &(ax, ay)) in tiles.iter().enumerate() {
+            for (j, &(bx, by)) in tiles.iter().enumerate() {
+                assert_eq!(views[i].is_in_fov(bx, by), views[j].is_in_fov(ax, ay),
+                           "{:?}, seed {}: ({}, {}) and ({}, {}) don't agree on whether they can see each other",
+                           algorithm, seed, ax, ay, bx, by);
+            }
+        }
+    }
+}
+
+#[test]
+fn shadowcasting_is_symmetric() {
+    assert_symmetric(FovAlgorithm::Shadowcasting, 24, 16);
+}
+
+#[test]
+fn permissive_is_symmetric() {
+    assert_symmetric(FovAlgorithm::Permissive, 16, 12);
+}
+
+#[test]
+fn whole_room_is_in_view() {
+    let map = make_test_map(12, 9, &[]);
+    for &algorithm in ALGORITHMS.iter() {
+        let fov_map = fov_from(&map, 3, 4, 0, true, algorithm);
+        for x in 0..12 {
+            for y in 0..9 {
+                assert!(fov_map.is_in_fov(x, y), "{:?}: ({}, {}) is not in view", algorithm, x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn walls_block_sight() {
+    // A wall across the room, from top to bottom.
+    let wall: Vec<(i32, i32)> = (0..9).map(|y| (6, y)).collect();
+    let map = make_test_map(12, 9, &wall);
+    for &algorithm in ALGORITHMS.iter() {
+        let fov_map = fov_from(&map, 3, 4, 0, true, algorithm);
+        assert!(fov_map.is_in_fov(3, 4), "{:?}: the viewer is not in view", algorithm);
+        assert!(fov_map.is_in_fov(6, 4), "{:?}: the wall is not in view", algorithm);
+        for x in 7..12 {
+            for y in 0..9 {
+                assert!(!fov_map.is_in_fov(x, y), "{:?}: ({}, {}) is in view through a wall", algorithm, x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn walls_only_in_view_when_lit() {
+    let map = make_random_map(16, 12, 4, 7);
+    for &algorithm in ALGORITHMS.iter() {
+        let fov_map = fov_from(&map, 8, 6, 0, false, algorithm);
+        for x in 0..16 {
+            for y in 0..12 {
+                if (x, y) != (8, 6) && map[x as usize][y as usize].block_sight {
+                    assert!(!fov_map.is_in_fov(x, y), "{:?}: wall ({}, {}) is in view", algorithm, x, y);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn nothing_in_view_beyond_radius() {
+    let map = make_test_map(30, 30, &[]);
+    for &algorithm in ALGORITHMS.iter() {
+        let fov_map = fov_from(&map, 15, 15, 5, true, algorithm);
+        assert!(fov_map.is_in_fov(15, 10) && fov_map.is_in_fov(18, 19), "{:?}: tiles in range are not in view", algorithm);
+        for x in 0..30 {
+            for y in 0..30 {
+                if (x - 15) * (x - 15) + (y - 15) * (y - 15) > 25 {
+                    assert!(!fov_map.is_in_fov(x, y), "{:?}: ({}, {}) is in view beyond the radius", algorithm, x, y);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn diagonal_gaps() {
+    // Two walls touching diagonally, with the viewer looking through the gap between them.
+    let map = make_test_map(9, 9, &[(4, 3), (5, 4)]);
+    assert!(fov_from(&map, 3, 5, 0, true, FovAlgorithm::Diamond).is_in_fov(6, 2));
+    assert!(!fov_from(&map, 3, 5, 0, true, FovAlgorithm::Permissive).is_in_fov(6, 2));
+}